@@ -1,9 +1,11 @@
+use board_repr_perft::common::CellGrid;
 use board_repr_perft::common::Piece;
 use board_repr_perft::common::PieceLocation;
+use board_repr_perft::eval::Dellacherie;
+use board_repr_perft::randomizer::{PieceGenerator, SevenBag};
 use board_repr_perft::*;
 use criterion::measurement::WallTime;
 use criterion::*;
-use rand::prelude::*;
 
 fn dellacherie(c: &mut Criterion) {
     fn bench<I: Implementation>(group: &mut BenchmarkGroup<WallTime>, pieces: &[Piece]) {
@@ -26,7 +28,9 @@ fn advance(c: &mut Criterion) {
     let mut board = <ColBits as Implementation>::Board::new();
     let mut placements = Vec::with_capacity(10000);
     for p in gen_seq(10000) {
-        if let Some(placement) = <ColBits as Implementation>::suggest(&board, p) {
+        if let Some(placement) =
+            <ColBits as Implementation>::suggest(&board, p, &<Dellacherie>::default())
+        {
             board.place(placement);
             board.collapse_lines();
             placements.push(placement);
@@ -56,29 +60,136 @@ fn advance(c: &mut Criterion) {
     bench::<ColBitsPext>(&mut group, &placements);
 }
 
+fn hold(c: &mut Criterion) {
+    let pieces = gen_seq(1001);
+    let mut board = <ColBits as Implementation>::Board::new();
+    let mut positions = Vec::with_capacity(1000);
+    for w in pieces.windows(2) {
+        positions.push((board.to_cells(), w[0], w[1]));
+        match <ColBits as Implementation>::suggest(&board, w[0], &<Dellacherie>::default()) {
+            Some(placement) => {
+                board.place(placement);
+                board.collapse_lines();
+            }
+            None => break,
+        }
+    }
+
+    fn bench<I: Implementation>(c: &mut Criterion, positions: &[(CellGrid, Piece, Piece)]) {
+        let positions: Vec<_> = positions
+            .iter()
+            .map(|(cells, current, next)| (I::Board::from_cells(cells), *current, *next))
+            .collect();
+        let mut group = c.benchmark_group(format!("hold/{}", I::NAME));
+        group.bench_function("no hold", |b| {
+            b.iter(|| {
+                for (board, current, _) in &positions {
+                    black_box(I::suggest(board, *current, &<Dellacherie>::default()));
+                }
+            })
+        });
+        group.bench_function("hold", |b| {
+            b.iter(|| {
+                for (board, current, next) in &positions {
+                    black_box(I::suggest_with_hold(
+                        board,
+                        *current,
+                        None,
+                        Some(*next),
+                        &<Dellacherie>::default(),
+                    ));
+                }
+            })
+        });
+    }
+
+    bench::<Naive>(c, &positions);
+    bench::<NaiveColHeights>(c, &positions);
+    bench::<RowBits>(c, &positions);
+    bench::<RowBitsColHeights>(c, &positions);
+    bench::<ColBits>(c, &positions);
+    bench::<ColBitsPext>(c, &positions);
+}
+
+fn lookahead(c: &mut Criterion) {
+    let pieces = gen_seq(13);
+    let mut board = <ColBits as Implementation>::Board::new();
+    let mut positions = Vec::with_capacity(10);
+    for w in pieces.windows(3).take(10) {
+        positions.push((board.to_cells(), [w[0], w[1], w[2]]));
+        match <ColBits as Implementation>::suggest(&board, w[0], &<Dellacherie>::default()) {
+            Some(placement) => {
+                board.place(placement);
+                board.collapse_lines();
+            }
+            None => break,
+        }
+    }
+
+    fn bench<I: Implementation>(
+        group: &mut BenchmarkGroup<WallTime>,
+        positions: &[(CellGrid, [Piece; 3])],
+    ) {
+        let positions: Vec<_> = positions
+            .iter()
+            .map(|(cells, queue)| (I::Board::from_cells(cells), *queue))
+            .collect();
+        group.bench_function(I::NAME, |b| {
+            b.iter(|| {
+                for (board, queue) in &positions {
+                    black_box(I::suggest_lookahead(
+                        board,
+                        queue,
+                        &<Dellacherie>::default(),
+                    ));
+                }
+            })
+        });
+    }
+
+    let mut group = c.benchmark_group("lookahead");
+    group.sample_size(10);
+
+    bench::<Naive>(&mut group, &positions);
+    bench::<NaiveColHeights>(&mut group, &positions);
+    bench::<RowBits>(&mut group, &positions);
+    bench::<RowBitsColHeights>(&mut group, &positions);
+    bench::<ColBits>(&mut group, &positions);
+    bench::<ColBitsPext>(&mut group, &positions);
+}
+
+fn pc(c: &mut Criterion) {
+    let queue = gen_seq(6);
+
+    fn bench<I: Implementation>(group: &mut BenchmarkGroup<WallTime>, queue: &[Piece]) {
+        let board = I::Board::new();
+        group.bench_function(I::NAME, |b| {
+            b.iter(|| black_box(pc::solve::<I>(&board, queue, None, true, 2)))
+        });
+    }
+
+    let mut group = c.benchmark_group("pc");
+    group.sample_size(10);
+
+    bench::<Naive>(&mut group, &queue);
+    bench::<NaiveColHeights>(&mut group, &queue);
+    bench::<RowBits>(&mut group, &queue);
+    bench::<RowBitsColHeights>(&mut group, &queue);
+    bench::<ColBits>(&mut group, &queue);
+    bench::<ColBitsPext>(&mut group, &queue);
+}
+
 criterion_group! {
     name = benchmarks;
     config = Criterion::default().measurement_time(std::time::Duration::from_secs(30));
-    targets = dellacherie, advance
+    targets = dellacherie, advance, hold, lookahead, pc
 }
 
 criterion_main!(benchmarks);
 
+const SEED: u64 = 0xcafef00dd15ea5e5;
+
+/// The piece sequence used by the benchmarks. Any `PieceGenerator` can be used here.
 fn gen_seq(n: usize) -> Vec<Piece> {
-    let mut rng = rand_pcg::Pcg64::new(0xcafef00dd15ea5e5, 0xa02bdbf7bb3c0a7ac28fa16a64abf96);
-
-    let mut piece_sequence = Vec::with_capacity(n);
-    for _ in 0..n {
-        piece_sequence.push(match rng.gen_range(0..7) {
-            0 => Piece::I,
-            1 => Piece::O,
-            2 => Piece::T,
-            3 => Piece::L,
-            4 => Piece::J,
-            5 => Piece::S,
-            6 => Piece::Z,
-            _ => unreachable!(),
-        })
-    }
-    piece_sequence
+    SevenBag::new(SEED).pieces(n)
 }
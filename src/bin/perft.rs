@@ -0,0 +1,198 @@
+use board_repr_perft::common::{Piece, PieceLocation};
+use board_repr_perft::movegen::DropMode;
+use board_repr_perft::perft::{divide, find_divergence, perft, perft_movegen};
+use board_repr_perft::rotation::{Ars, Nes, Srs};
+use board_repr_perft::rules::Rules;
+use board_repr_perft::*;
+use std::time::Instant;
+
+const USAGE: &str = "usage: perft [--divide | --compare | --rules <system> [--soft-drop]]
+                    <representation> <queue> [board file]
+
+Counts placement sequences of the pieces in the queue (e.g. TIOLJSZ) at each depth up to the
+length of the queue, starting from an empty board or the board in the given file.
+
+--divide     prints the count below each placement of the first piece instead
+--compare    finds the first placement sequence where the count differs from naive
+--rules      counts the placements found by move generation under the guideline rules instead,
+             with block-out and lock-out, using the rotation system <system>: srs, srs180
+             (srs with 180 degree rotations using the SRS+ kicks), ars or nes
+--soft-drop  with --rules, also counts tucks and slides that need soft drops
+
+representations: naive, naive_col_heights, row_bits, row_bits_col_heights, column_bits,
+column_bits_pext";
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Perft,
+    Divide,
+    Compare,
+    Movegen(Movegen),
+}
+
+#[derive(Clone, Copy)]
+enum System {
+    Srs,
+    Ars,
+    Nes,
+}
+
+#[derive(Clone, Copy)]
+struct Movegen {
+    rules: Rules,
+    system: System,
+    drop_mode: DropMode,
+}
+
+fn main() {
+    let mut mode = Mode::Perft;
+    let mut system = None;
+    let mut drop_mode = DropMode::HardDrop;
+    let mut args = vec![];
+    let mut all_args = std::env::args().skip(1);
+    while let Some(arg) = all_args.next() {
+        match &*arg {
+            "--divide" => mode = Mode::Divide,
+            "--compare" => mode = Mode::Compare,
+            "--rules" => system = Some(all_args.next().unwrap_or_else(|| exit_with(USAGE))),
+            "--soft-drop" => drop_mode = DropMode::SoftDrop,
+            _ if arg.starts_with("--") => exit_with(USAGE),
+            _ => args.push(arg),
+        }
+    }
+    if args.len() < 2 || args.len() > 3 {
+        exit_with(USAGE);
+    }
+    if let Some(name) = system {
+        let (system, allow_180) = match &*name {
+            "srs" => (System::Srs, false),
+            "srs180" => (System::Srs, true),
+            "ars" => (System::Ars, false),
+            "nes" => (System::Nes, false),
+            other => exit_with(&format!("unknown rotation system {:?}\n\n{}", other, USAGE)),
+        };
+        if !matches!(mode, Mode::Perft) {
+            exit_with("--rules can't be combined with --divide or --compare");
+        }
+        let rules = Rules {
+            allow_180,
+            ..Rules::default()
+        };
+        mode = Mode::Movegen(Movegen {
+            rules,
+            system,
+            drop_mode,
+        });
+    } else if drop_mode == DropMode::SoftDrop {
+        exit_with("--soft-drop needs --rules");
+    }
+
+    let queue: Vec<Piece> = args[1]
+        .chars()
+        .map(|c| {
+            Piece::from_char(c).unwrap_or_else(|| exit_with(&format!("invalid piece {:?}", c)))
+        })
+        .collect();
+    let board = match args.get(2) {
+        None => String::new(),
+        Some(path) => std::fs::read_to_string(path)
+            .unwrap_or_else(|e| exit_with(&format!("could not read {}: {}", path, e))),
+    };
+    let board = board
+        .parse::<naive::Board>()
+        .unwrap_or_else(|e| exit_with(&format!("invalid board: {}", e)));
+
+    match &*args[0] {
+        "naive" => run::<Naive>(mode, &board, &queue),
+        "naive_col_heights" => run::<NaiveColHeights>(mode, &board, &queue),
+        "row_bits" => run::<RowBits>(mode, &board, &queue),
+        "row_bits_col_heights" => run::<RowBitsColHeights>(mode, &board, &queue),
+        "column_bits" => run::<ColBits>(mode, &board, &queue),
+        "column_bits_pext" => run::<ColBitsPext>(mode, &board, &queue),
+        other => exit_with(&format!("unknown representation {:?}\n\n{}", other, USAGE)),
+    }
+}
+
+fn run<I: Implementation>(mode: Mode, reference: &naive::Board, queue: &[Piece])
+where
+    I::Board: Copy,
+{
+    let board = reference.convert::<I::Board>();
+    println!("{}", I::NAME);
+    match mode {
+        Mode::Perft | Mode::Movegen(_) => {
+            for depth in 1..=queue.len() {
+                let start = Instant::now();
+                let nodes = match mode {
+                    Mode::Movegen(movegen) => count(&movegen, &board, &queue[..depth]),
+                    _ => perft::<I>(&board, &queue[..depth]),
+                };
+                let elapsed = start.elapsed();
+                println!(
+                    "depth {}: {} nodes in {:.3?} ({:.0} nodes/s)",
+                    depth,
+                    nodes,
+                    elapsed,
+                    nodes as f64 / elapsed.as_secs_f64()
+                );
+            }
+        }
+        Mode::Divide => {
+            let start = Instant::now();
+            let counts = divide::<I>(&board, queue);
+            let elapsed = start.elapsed();
+            for &(location, count) in &counts {
+                println!("{}: {}", format_location(location), count);
+            }
+            let nodes: u64 = counts.iter().map(|&(_, count)| count).sum();
+            println!(
+                "total: {} nodes in {:.3?} ({:.0} nodes/s)",
+                nodes,
+                elapsed,
+                nodes as f64 / elapsed.as_secs_f64()
+            );
+        }
+        Mode::Compare => match find_divergence::<Naive, I>(reference, &board, queue) {
+            None => println!("matches naive"),
+            Some(divergence) => {
+                for &location in &divergence.path {
+                    println!("{}", format_location(location));
+                }
+                let count =
+                    |count: Option<u64>| count.map_or("missing".to_string(), |c| c.to_string());
+                println!(
+                    "{}: naive {}, {} {}",
+                    format_location(divergence.location),
+                    count(divergence.counts[0]),
+                    I::NAME,
+                    count(divergence.counts[1])
+                );
+            }
+        },
+    }
+}
+
+fn count<B: BoardImpl + Copy>(movegen: &Movegen, board: &B, queue: &[Piece]) -> u64 {
+    let Movegen {
+        rules,
+        system,
+        drop_mode,
+    } = movegen;
+    match system {
+        System::Srs => perft_movegen(rules, &Srs, board, queue, *drop_mode),
+        System::Ars => perft_movegen(rules, &Ars, board, queue, *drop_mode),
+        System::Nes => perft_movegen(rules, &Nes, board, queue, *drop_mode),
+    }
+}
+
+fn format_location(location: PieceLocation) -> String {
+    format!(
+        "{:?} {:?} {},{}",
+        location.piece, location.rotation, location.x, location.y
+    )
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1)
+}
@@ -1,4 +1,12 @@
-use crate::{common::*, BoardImpl, Implementation};
+use crate::{
+    common::*,
+    eval::PlacementInfo,
+    notation::{self, ParseBoardError},
+    rules::TopOut,
+    BoardImpl, Features, Implementation,
+};
+use std::fmt;
+use std::str::FromStr;
 
 pub struct ColBitsPext;
 
@@ -29,9 +37,7 @@ impl BoardImpl for Board {
         }
         line_clear_mask.count_ones() as i32
     }
-}
 
-impl Board {
     fn get(&self, x: i8, y: i8) -> bool {
         self.columns[x as usize] & 1 << y != 0
     }
@@ -40,6 +46,59 @@ impl Board {
         64 - self.columns[x as usize].leading_zeros() as i8
     }
 
+    fn is_row_full(&self, y: i8) -> bool {
+        self.line_clear_mask() & 1 << y != 0
+    }
+
+    fn is_row_empty(&self, y: i8) -> bool {
+        self.columns.iter().fold(0, |a, b| a | b) & 1 << y == 0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.columns.iter().all(|&c| c == 0)
+    }
+
+    fn corners(&self, x: i8, y: i8) -> u8 {
+        // the column with the floor added, so that bit y + 1 is row y
+        let column = |x: i8| match x {
+            0..=9 => self.columns[x as usize] << 1 | 1,
+            _ => !0,
+        };
+        let left = column(x - 1) >> y;
+        let right = column(x + 1) >> y;
+        (left >> 2 & 1 | (right >> 2 & 1) << 1 | (left & 1) << 2 | (right & 1) << 3) as u8
+    }
+
+    fn add_garbage_holes(&mut self, rows: u32, holes: u16) -> Result<(), TopOut> {
+        let rows = rows.min(40);
+        let garbage = (1 << rows) - 1;
+        let mut topped_out = false;
+        for (x, column) in self.columns.iter_mut().enumerate() {
+            topped_out |= *column >> (40 - rows) != 0;
+            *column = *column << rows & ((1 << 40) - 1);
+            if holes & 1 << x == 0 {
+                *column |= garbage;
+            }
+        }
+        if topped_out {
+            Err(TopOut::GarbageOut)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn from_cells(cells: &CellGrid) -> Self {
+        let mut board = Board::new();
+        for (y, row) in cells.iter().enumerate() {
+            for (column, &cell) in board.columns.iter_mut().zip(row) {
+                *column |= (cell as u64) << y;
+            }
+        }
+        board
+    }
+}
+
+impl Board {
     fn distance_to_ground(self, x: i8, y: i8) -> i8 {
         (!self.columns[x as usize] << (63 - y)).leading_ones() as i8
     }
@@ -47,19 +106,19 @@ impl Board {
     fn line_clear_mask(&self) -> u64 {
         self.columns.iter().fold(!0, |a, b| a & b)
     }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        notation::write_board(self, f)
+    }
+}
 
-    #[cfg(test)]
-    pub fn fumenize(self) -> fumen::Fumen {
-        let mut fumen = fumen::Fumen::default();
-        let page = fumen.add_page();
-        for y in 0..23 {
-            for x in 0..10 {
-                if self.get(x as i8, y as i8) {
-                    page.field[y][x] = fumen::CellColor::Grey;
-                }
-            }
-        }
-        fumen
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, ParseBoardError> {
+        notation::parse_board(s)
     }
 }
 
@@ -68,9 +127,7 @@ impl Implementation for ColBitsPext {
 
     const NAME: &'static str = "col bits pext";
 
-    fn suggest(board: &Board, piece: Piece) -> Option<PieceLocation> {
-        let mut best = None;
-
+    fn for_each_placement(board: &Board, piece: Piece, mut f: impl FnMut(&Board, &PlacementInfo)) {
         for &rotation in piece.sensible_rotations() {
             'placement: for x in 0..10 {
                 let mut piece = PieceLocation {
@@ -81,13 +138,17 @@ impl Implementation for ColBitsPext {
                 };
 
                 for &(x, y) in &piece.cells() {
-                    if x < 0 || x >= 10 {
+                    if !(0..10).contains(&x) {
                         continue 'placement;
                     }
 
                     piece.y = piece.y.max(board.column_height(x) - y);
                 }
 
+                if piece.cells().iter().any(|&(_, y)| y >= 40) {
+                    continue;
+                }
+
                 let mut board = *board;
                 board.place(piece);
 
@@ -100,86 +161,64 @@ impl Implementation for ColBitsPext {
 
                 let lines_cleared = board.collapse_lines();
 
-                let mut low = 40;
-                let mut high = 0;
-                for &(_, y) in &piece.cells() {
-                    low = low.min(y);
-                    high = high.max(y);
-                }
-
-                let landing_height = low as i32 + high as i32;
-                let eroded_piece_cells_metric = lines_cleared * piece_cells_eliminated;
-                let row_transitions = row_transitions(&board);
-                let column_transitions = column_transitions(&board);
-                let buried_holes = buried_holes(&board);
-                let wells = wells(&board);
-
-                let score = 2 * eroded_piece_cells_metric
-                    - landing_height
-                    - 2 * row_transitions
-                    - 2 * column_transitions
-                    - 8 * buried_holes
-                    - 2 * wells;
-
-                match best {
-                    None => best = Some((piece, score)),
-                    Some((_, s)) => {
-                        if score > s {
-                            best = Some((piece, score))
-                        }
-                    }
-                }
+                f(
+                    &board,
+                    &PlacementInfo {
+                        location: piece,
+                        lines_cleared,
+                        piece_cells_eliminated,
+                        perfect_clear: lines_cleared > 0 && board.is_empty(),
+                    },
+                );
             }
         }
-
-        best.map(|(p, _)| p)
     }
 }
 
-fn row_transitions(board: &Board) -> i32 {
-    let left_side = board.columns[0].count_zeros() as i32;
-    let right_side = board.columns[9].count_zeros() as i32;
-    board
-        .columns
-        .windows(2)
-        .map(|cs| (cs[0] ^ cs[1]).count_ones() as i32)
-        .sum::<i32>()
-        + left_side
-        + right_side
-}
+impl Features for Board {
+    fn row_transitions(&self) -> i32 {
+        // only the 40 rows of the board count, not the whole u64
+        let left_side = 40 - self.columns[0].count_ones() as i32;
+        let right_side = 40 - self.columns[9].count_ones() as i32;
+        self.columns
+            .windows(2)
+            .map(|cs| (cs[0] ^ cs[1]).count_ones() as i32)
+            .sum::<i32>()
+            + left_side
+            + right_side
+    }
 
-fn column_transitions(board: &Board) -> i32 {
-    board
-        .columns
-        .iter()
-        .map(|&c| (c ^ (c << 1 | 1)).count_ones() as i32)
-        .sum()
-}
+    fn column_transitions(&self) -> i32 {
+        self.columns
+            .iter()
+            .map(|&c| (c ^ (c << 1 | 1)).count_ones() as i32)
+            .sum()
+    }
 
-fn buried_holes(board: &Board) -> i32 {
-    board
-        .columns
-        .iter()
-        .map(|&c| {
-            let covered_mask = (1 << (64 - c.leading_zeros())) - 1;
-            (!c & covered_mask).count_ones() as i32
-        })
-        .sum()
-}
+    fn buried_holes(&self) -> i32 {
+        self.columns
+            .iter()
+            .map(|&c| {
+                let covered_mask = (1 << (64 - c.leading_zeros())) - 1;
+                (!c & covered_mask).count_ones() as i32
+            })
+            .sum()
+    }
 
-fn wells(board: &Board) -> i32 {
-    let mut cumulative_wells = 0;
-    for x in 0..10 {
-        let left = if x == 0 { !0 } else { board.columns[x - 1] };
-        let right = if x == 9 { !0 } else { board.columns[x + 1] };
+    fn wells(&self) -> i32 {
+        let mut cumulative_wells = 0;
+        for x in 0..10 {
+            let left = if x == 0 { !0 } else { self.columns[x - 1] };
+            let right = if x == 9 { !0 } else { self.columns[x + 1] };
 
-        let mut wells = left & right & !board.columns[x];
+            let mut wells = left & right & !self.columns[x];
 
-        while wells != 0 {
-            let y = wells.trailing_zeros();
-            cumulative_wells += board.distance_to_ground(x as i8, y as i8) as i32;
-            wells &= !(1 << y);
+            while wells != 0 {
+                let y = wells.trailing_zeros();
+                cumulative_wells += self.distance_to_ground(x as i8, y as i8) as i32;
+                wells &= !(1 << y);
+            }
         }
+        cumulative_wells
     }
-    cumulative_wells
 }
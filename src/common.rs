@@ -1,4 +1,19 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The guideline spawn position: the pivot of a north facing piece is in the fifth column of the
+/// first row above the 20 row visible field.
+pub const SPAWN_X: i8 = 4;
+pub const SPAWN_Y: i8 = 20;
+
+/// Bits of the mask returned by `BoardImpl::corners`.
+pub const CORNER_NW: u8 = 1;
+pub const CORNER_NE: u8 = 2;
+pub const CORNER_SW: u8 = 4;
+pub const CORNER_SE: u8 = 8;
+
+/// Row-major grid of filled cells, indexed as `[y][x]` with `y = 0` the bottom row. This is the
+/// canonical form used to convert between board representations.
+pub type CellGrid = [[bool; 10]; 40];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PieceLocation {
     pub piece: Piece,
     pub rotation: Rotation,
@@ -6,7 +21,7 @@ pub struct PieceLocation {
     pub y: i8,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Piece {
     I,
     O,
@@ -16,7 +31,7 @@ pub enum Piece {
     S,
     Z,
 }
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Rotation {
     North,
     West,
@@ -25,6 +40,16 @@ pub enum Rotation {
 }
 
 impl Piece {
+    pub const ALL: [Piece; 7] = [
+        Piece::I,
+        Piece::O,
+        Piece::T,
+        Piece::L,
+        Piece::J,
+        Piece::S,
+        Piece::Z,
+    ];
+
     pub const fn cells(self) -> [(i8, i8); 4] {
         match self {
             Piece::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
@@ -37,21 +62,64 @@ impl Piece {
         }
     }
 
+    /// Parses a piece from its letter, in either case.
+    pub fn from_char(c: char) -> Option<Piece> {
+        match c.to_ascii_uppercase() {
+            'I' => Some(Piece::I),
+            'O' => Some(Piece::O),
+            'T' => Some(Piece::T),
+            'L' => Some(Piece::L),
+            'J' => Some(Piece::J),
+            'S' => Some(Piece::S),
+            'Z' => Some(Piece::Z),
+            _ => None,
+        }
+    }
+
     pub const fn sensible_rotations(self) -> &'static [Rotation] {
         match self {
             Piece::O => &[Rotation::North],
             Piece::I | Piece::S | Piece::Z => &[Rotation::North, Rotation::East],
-            Piece::T | Piece::L | Piece::J => &[
-                Rotation::North,
-                Rotation::East,
-                Rotation::South,
-                Rotation::West,
-            ],
+            Piece::T | Piece::L | Piece::J => &Rotation::ALL,
         }
     }
 }
 
 impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::North,
+        Rotation::East,
+        Rotation::South,
+        Rotation::West,
+    ];
+
+    pub const fn cw(self) -> Rotation {
+        match self {
+            Rotation::North => Rotation::East,
+            Rotation::East => Rotation::South,
+            Rotation::South => Rotation::West,
+            Rotation::West => Rotation::North,
+        }
+    }
+
+    pub const fn ccw(self) -> Rotation {
+        match self {
+            Rotation::North => Rotation::West,
+            Rotation::West => Rotation::South,
+            Rotation::South => Rotation::East,
+            Rotation::East => Rotation::North,
+        }
+    }
+
+    pub const fn flip(self) -> Rotation {
+        match self {
+            Rotation::North => Rotation::South,
+            Rotation::East => Rotation::West,
+            Rotation::South => Rotation::North,
+            Rotation::West => Rotation::East,
+        }
+    }
+
     pub const fn rotate_cell(self, (x, y): (i8, i8)) -> (i8, i8) {
         match self {
             Rotation::North => (x, y),
@@ -73,7 +141,50 @@ impl PieceLocation {
         ]
     }
 
+    /// The location with the same cells whose rotation is one of the piece's
+    /// `sensible_rotations`, so equal placements compare equal.
+    pub const fn canonical(self) -> PieceLocation {
+        let (rotation, dx, dy) = match (self.piece, self.rotation) {
+            (Piece::I, Rotation::South) => (Rotation::North, -1, 0),
+            (Piece::I, Rotation::West) => (Rotation::East, 0, 1),
+            (Piece::S, Rotation::South) | (Piece::Z, Rotation::South) => (Rotation::North, 0, -1),
+            (Piece::S, Rotation::West) | (Piece::Z, Rotation::West) => (Rotation::East, -1, 0),
+            (Piece::O, Rotation::East) => (Rotation::North, 0, -1),
+            (Piece::O, Rotation::South) => (Rotation::North, -1, -1),
+            (Piece::O, Rotation::West) => (Rotation::North, -1, 0),
+            (_, rotation) => (rotation, 0, 0),
+        };
+        PieceLocation {
+            piece: self.piece,
+            rotation,
+            x: self.x + dx,
+            y: self.y + dy,
+        }
+    }
+
     const fn translate(self, (x, y): (i8, i8)) -> (i8, i8) {
         (x + self.x, y + self.y)
     }
 }
+
+#[cfg(test)]
+#[test]
+fn canonical_same_cells() {
+    for &piece in &Piece::ALL {
+        for &rotation in &Rotation::ALL {
+            let location = PieceLocation {
+                piece,
+                rotation,
+                x: 4,
+                y: 10,
+            };
+            let canonical = location.canonical();
+            assert!(piece.sensible_rotations().contains(&canonical.rotation));
+            let mut cells = location.cells();
+            let mut canonical_cells = canonical.cells();
+            cells.sort();
+            canonical_cells.sort();
+            assert_eq!(cells, canonical_cells);
+        }
+    }
+}
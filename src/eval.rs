@@ -0,0 +1,149 @@
+use crate::common::{PieceLocation, Rotation, SPAWN_X};
+use crate::Features;
+use std::ops::{Add, Mul};
+
+/// What happened when a piece was placed, for evaluators that need more than the resulting board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlacementInfo {
+    pub location: PieceLocation,
+    pub lines_cleared: i32,
+    /// The number of cells of the placed piece that were removed by the line clear.
+    pub piece_cells_eliminated: i32,
+    /// Whether the line clear left the board empty.
+    pub perfect_clear: bool,
+}
+
+/// Scores a board after a piece has been placed and lines have been cleared. Higher scores are
+/// better.
+pub trait Evaluator<B> {
+    type Score: PartialOrd + Copy;
+
+    fn evaluate(&self, board: &B, placement: &PlacementInfo) -> Self::Score;
+}
+
+/// Pierre Dellacherie's one-piece evaluation function, with configurable weights.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Dellacherie<T = i32> {
+    pub weights: DellacherieWeights<T>,
+}
+
+/// The coefficient of each term in Dellacherie's evaluation function. The default weights are the
+/// ones from the original algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DellacherieWeights<T> {
+    pub eroded_piece_cells: T,
+    /// Applied to the sum of the lowest and highest y coordinates of the placed piece.
+    pub landing_height: T,
+    pub row_transitions: T,
+    pub column_transitions: T,
+    pub buried_holes: T,
+    pub wells: T,
+}
+
+/// A numeric type usable as a Dellacherie weight and score.
+pub trait Weight: Copy + PartialOrd + Add<Output = Self> + Mul<Output = Self> {
+    fn from_i32(v: i32) -> Self;
+}
+
+impl Weight for i32 {
+    fn from_i32(v: i32) -> Self {
+        v
+    }
+}
+
+impl Weight for i64 {
+    fn from_i32(v: i32) -> Self {
+        v as i64
+    }
+}
+
+impl Weight for f32 {
+    fn from_i32(v: i32) -> Self {
+        v as f32
+    }
+}
+
+impl Weight for f64 {
+    fn from_i32(v: i32) -> Self {
+        v as f64
+    }
+}
+
+impl<T: Weight> Default for DellacherieWeights<T> {
+    fn default() -> Self {
+        DellacherieWeights {
+            eroded_piece_cells: T::from_i32(2),
+            landing_height: T::from_i32(-1),
+            row_transitions: T::from_i32(-2),
+            column_transitions: T::from_i32(-2),
+            buried_holes: T::from_i32(-8),
+            wells: T::from_i32(-2),
+        }
+    }
+}
+
+impl<T: Weight> Default for Dellacherie<T> {
+    fn default() -> Self {
+        Dellacherie::new(DellacherieWeights::default())
+    }
+}
+
+impl<T> Dellacherie<T> {
+    pub fn new(weights: DellacherieWeights<T>) -> Self {
+        Dellacherie { weights }
+    }
+}
+
+impl<T: Weight, B: Features> Evaluator<B> for Dellacherie<T> {
+    type Score = T;
+
+    fn evaluate(&self, board: &B, placement: &PlacementInfo) -> T {
+        let mut low = 40;
+        let mut high = 0;
+        for &(_, y) in &placement.location.cells() {
+            low = low.min(y);
+            high = high.max(y);
+        }
+
+        let landing_height = low as i32 + high as i32;
+        let eroded_piece_cells_metric = placement.lines_cleared * placement.piece_cells_eliminated;
+
+        let w = &self.weights;
+        w.eroded_piece_cells * T::from_i32(eroded_piece_cells_metric)
+            + w.landing_height * T::from_i32(landing_height)
+            + w.row_transitions * T::from_i32(board.row_transitions())
+            + w.column_transitions * T::from_i32(board.column_transitions())
+            + w.buried_holes * T::from_i32(board.buried_holes())
+            + w.wells * T::from_i32(board.wells())
+    }
+}
+
+/// Wraps an evaluator to break ties between equal scores using the move priority from Colin
+/// Fahey's implementation of Dellacherie's algorithm, so results don't depend on the order that
+/// placements are enumerated in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TieBreak<E>(pub E);
+
+impl<B, E: Evaluator<B>> Evaluator<B> for TieBreak<E> {
+    type Score = (E::Score, i32);
+
+    fn evaluate(&self, board: &B, placement: &PlacementInfo) -> Self::Score {
+        (
+            self.0.evaluate(board, placement),
+            priority(placement.location),
+        )
+    }
+}
+
+/// `100 * |dx| + 10 * (dx > 0) - rotations`, where `dx` is the horizontal distance from the spawn
+/// column and `rotations` is the number of clockwise rotations from spawn. Higher is preferred.
+pub fn priority(location: PieceLocation) -> i32 {
+    let dx = (location.x - SPAWN_X) as i32;
+    let rotations = match location.rotation {
+        Rotation::North => 0,
+        Rotation::East => 1,
+        Rotation::South => 2,
+        Rotation::West => 3,
+    };
+    100 * dx.abs() + if dx > 0 { 10 } else { 0 } - rotations
+}
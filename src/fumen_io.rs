@@ -0,0 +1,153 @@
+use crate::{common::*, BoardImpl};
+use fumen::{CellColor, Fumen, PieceType, RotationState};
+
+/// The number of rows in a fumen field, not counting the garbage row.
+pub const FUMEN_HEIGHT: i8 = 23;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FumenError {
+    Decode(fumen::DecodeFumenError),
+    NoPages,
+    /// The board or a placement has cells above the 23 rows a fumen field can hold.
+    TooTall,
+}
+
+impl std::fmt::Display for FumenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FumenError::Decode(e) => e.fmt(f),
+            FumenError::NoPages => write!(f, "the fumen has no pages"),
+            FumenError::TooTall => write!(f, "the board does not fit in a fumen field"),
+        }
+    }
+}
+
+impl std::error::Error for FumenError {}
+
+impl From<fumen::DecodeFumenError> for FumenError {
+    fn from(e: fumen::DecodeFumenError) -> Self {
+        FumenError::Decode(e)
+    }
+}
+
+/// Decodes the field of the first page of a fumen data string. Colored and grey cells are both
+/// treated as filled.
+pub fn decode<B: BoardImpl>(data: &str) -> Result<B, FumenError> {
+    from_fumen(&Fumen::decode(data)?)
+}
+
+pub fn from_fumen<B: BoardImpl>(fumen: &Fumen) -> Result<B, FumenError> {
+    let page = fumen.pages.first().ok_or(FumenError::NoPages)?;
+    let mut cells = [[false; 10]; 40];
+    for (row, fumen_row) in cells.iter_mut().zip(&page.field) {
+        for (cell, &color) in row.iter_mut().zip(fumen_row) {
+            *cell = color != CellColor::Empty;
+        }
+    }
+    Ok(B::from_cells(&cells))
+}
+
+/// Exports the board as a single page fumen with grey cells.
+///
+/// Every row of the board is exported, but fumen fields are only 23 rows tall, so this fails if
+/// anything is filled above that.
+pub fn to_fumen<B: BoardImpl>(board: &B) -> Result<Fumen, FumenError> {
+    let mut fumen = Fumen::default();
+    let page = fumen.add_page();
+    for y in 0..40 {
+        for x in 0..10 {
+            if !board.get(x, y) {
+                continue;
+            }
+            if y >= FUMEN_HEIGHT {
+                return Err(FumenError::TooTall);
+            }
+            page.field[y as usize][x as usize] = CellColor::Grey;
+        }
+    }
+    Ok(fumen)
+}
+
+/// Exports the board followed by a sequence of placements, with one page per placement. The
+/// placed pieces are colored and line clears are applied by fumen as the pages advance.
+pub fn placements_to_fumen<B: BoardImpl>(
+    board: &B,
+    placements: &[PieceLocation],
+) -> Result<Fumen, FumenError> {
+    let mut fumen = to_fumen(board)?;
+    for (i, &placement) in placements.iter().enumerate() {
+        let piece = fumen_piece(placement)?;
+        let page = match i {
+            0 => &mut fumen.pages[0],
+            _ => fumen.add_page(),
+        };
+        page.piece = Some(piece);
+    }
+    Ok(fumen)
+}
+
+fn fumen_piece(placement: PieceLocation) -> Result<fumen::Piece, FumenError> {
+    if placement
+        .cells()
+        .iter()
+        .any(|&(x, y)| !(0..10).contains(&x) || !(0..FUMEN_HEIGHT).contains(&y))
+    {
+        return Err(FumenError::TooTall);
+    }
+    Ok(fumen::Piece {
+        kind: match placement.piece {
+            Piece::I => PieceType::I,
+            Piece::O => PieceType::O,
+            Piece::T => PieceType::T,
+            Piece::L => PieceType::L,
+            Piece::J => PieceType::J,
+            Piece::S => PieceType::S,
+            Piece::Z => PieceType::Z,
+        },
+        rotation: match placement.rotation {
+            Rotation::North => RotationState::North,
+            Rotation::East => RotationState::East,
+            Rotation::South => RotationState::South,
+            Rotation::West => RotationState::West,
+        },
+        x: placement.x as u32,
+        y: placement.y as u32,
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn round_trip() {
+    use crate::eval::Dellacherie;
+    use crate::{column_bits, naive, row_bits, Implementation, Naive};
+
+    let sequence = [
+        Piece::T,
+        Piece::I,
+        Piece::O,
+        Piece::L,
+        Piece::J,
+        Piece::S,
+        Piece::Z,
+    ];
+    let mut board = naive::Board::new();
+    let mut placements = vec![];
+    for &piece in sequence.iter().cycle().take(30) {
+        let placement = Naive::suggest(&board, piece, &<Dellacherie>::default()).unwrap();
+        board.place(placement);
+        board.collapse_lines();
+        placements.push(placement);
+    }
+
+    let data = to_fumen(&board).unwrap().encode();
+    assert_eq!(decode::<naive::Board>(&data), Ok(board));
+    assert_eq!(decode::<row_bits::Board>(&data), Ok(board.convert()));
+    assert_eq!(decode::<column_bits::Board>(&data), Ok(board.convert()));
+
+    let fumen = placements_to_fumen(&naive::Board::new(), &placements).unwrap();
+    assert_eq!(fumen.pages.len(), placements.len());
+    let last = fumen.pages.last().unwrap().next_page();
+    let mut final_page = Fumen::default();
+    final_page.pages.push(last);
+    assert_eq!(from_fumen::<naive::Board>(&final_page), Ok(board));
+}
@@ -0,0 +1,226 @@
+//! Guideline scoring on top of a board.
+
+use crate::common::*;
+use crate::movegen::Placement;
+use crate::tspin::TSpin;
+use crate::{BoardImpl, PlacementError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Game<B> {
+    pub board: B,
+    pub score: u32,
+    /// Multiplies every score. It doesn't change by itself.
+    pub level: u32,
+    pub lines: u32,
+    /// The number of placements in a row that cleared lines, minus one, or `None` if the last
+    /// placement didn't clear any.
+    pub combo: Option<u32>,
+    /// Whether the last line clear was a tetris or a T-spin, so the next one of those gets the
+    /// back-to-back bonus.
+    pub back_to_back: bool,
+}
+
+/// What happened when a piece locked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LockResult {
+    pub lines_cleared: i32,
+    pub tspin: TSpin,
+    pub perfect_clear: bool,
+    /// Whether the back-to-back bonus was awarded.
+    pub back_to_back: bool,
+    pub combo: Option<u32>,
+    /// The points scored by this placement.
+    pub score: u32,
+}
+
+impl<B: BoardImpl> Game<B> {
+    pub fn new() -> Self {
+        Game::with_board(B::new())
+    }
+
+    pub fn with_board(board: B) -> Self {
+        Game {
+            board,
+            score: 0,
+            level: 1,
+            lines: 0,
+            combo: None,
+            back_to_back: false,
+        }
+    }
+
+    /// Places the piece, clears lines and scores the placement.
+    pub fn lock(&mut self, placement: Placement) -> Result<LockResult, PlacementError> {
+        self.board.try_place(placement.location)?;
+        let lines_cleared = self.board.collapse_lines();
+        let tspin = match placement.location.piece {
+            Piece::T => placement.tspin,
+            _ => TSpin::None,
+        };
+        let perfect_clear = lines_cleared > 0 && self.board.is_empty();
+
+        let mut back_to_back = false;
+        let mut score = action_score(lines_cleared, tspin);
+        if lines_cleared > 0 {
+            let difficult = lines_cleared == 4 || tspin != TSpin::None;
+            back_to_back = difficult && self.back_to_back;
+            if back_to_back {
+                score += score / 2;
+            }
+            self.back_to_back = difficult;
+
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+            score += 50 * self.combo.unwrap();
+
+            if perfect_clear {
+                score += perfect_clear_score(lines_cleared, back_to_back);
+            }
+        } else {
+            self.combo = None;
+        }
+
+        let score = score * self.level;
+        self.score += score;
+        self.lines += lines_cleared as u32;
+        Ok(LockResult {
+            lines_cleared,
+            tspin,
+            perfect_clear,
+            back_to_back,
+            combo: self.combo,
+            score,
+        })
+    }
+}
+
+impl<B: BoardImpl> Default for Game<B> {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+/// The guideline score for a line clear at level 1, before bonuses.
+pub fn action_score(lines_cleared: i32, tspin: TSpin) -> u32 {
+    match (tspin, lines_cleared) {
+        (TSpin::None, 0) => 0,
+        (TSpin::None, 1) => 100,
+        (TSpin::None, 2) => 300,
+        (TSpin::None, 3) => 500,
+        (TSpin::None, _) => 800,
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, 1) => 200,
+        (TSpin::Mini, _) => 400,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, _) => 1600,
+    }
+}
+
+fn perfect_clear_score(lines_cleared: i32, back_to_back: bool) -> u32 {
+    match lines_cleared {
+        1 => 800,
+        2 => 1200,
+        3 => 1800,
+        _ if back_to_back => 3200,
+        _ => 2000,
+    }
+}
+
+#[cfg(test)]
+fn placement(piece: Piece, rotation: Rotation, x: i8, y: i8, tspin: TSpin) -> Placement {
+    Placement {
+        location: PieceLocation {
+            piece,
+            rotation,
+            x,
+            y,
+        },
+        tspin,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn back_to_back_tetrises() {
+    use crate::naive;
+
+    let board: naive::Board = "
+        .#########
+        .#########
+        .#########
+        .#########
+        .#########
+        .#########
+        .#########
+        .#########
+    "
+    .parse()
+    .unwrap();
+    let mut game = Game::with_board(board);
+    let i = placement(Piece::I, Rotation::East, 0, 2, TSpin::None);
+    let first = game.lock(i).unwrap();
+    assert_eq!(first.lines_cleared, 4);
+    assert!(!first.back_to_back);
+    assert_eq!(first.score, 800);
+
+    let second = game
+        .lock(placement(Piece::I, Rotation::East, 0, 2, TSpin::None))
+        .unwrap();
+    assert!(second.back_to_back);
+    assert!(second.perfect_clear);
+    assert_eq!(second.combo, Some(1));
+    assert_eq!(second.score, 1200 + 50 + 3200);
+    assert_eq!(game.score, 800 + 4450);
+    assert_eq!(game.lines, 8);
+}
+
+#[cfg(test)]
+#[test]
+fn tspin_double_and_combo_break() {
+    use crate::naive;
+
+    let board: naive::Board = "
+        ###.......
+        ##...#####
+        ###.######
+    "
+    .parse()
+    .unwrap();
+    let mut game = Game::with_board(board);
+    let tsd = game
+        .lock(placement(Piece::T, Rotation::South, 3, 1, TSpin::Full))
+        .unwrap();
+    assert_eq!(tsd.lines_cleared, 2);
+    assert_eq!(tsd.score, 1200);
+    assert!(game.back_to_back);
+
+    let miss = game
+        .lock(placement(Piece::O, Rotation::North, 8, 0, TSpin::None))
+        .unwrap();
+    assert_eq!(miss.score, 0);
+    assert_eq!(game.combo, None);
+    assert!(game.back_to_back);
+}
+
+#[cfg(test)]
+#[test]
+fn perfect_clear() {
+    use crate::naive;
+
+    let board: naive::Board = "
+        #########.
+        #########.
+        #########.
+        #########.
+    "
+    .parse()
+    .unwrap();
+    let mut game = Game::with_board(board);
+    let result = game
+        .lock(placement(Piece::I, Rotation::East, 9, 2, TSpin::None))
+        .unwrap();
+    assert!(result.perfect_clear);
+    assert_eq!(result.score, 800 + 2000);
+    assert!(game.board.is_empty());
+}
@@ -1,78 +1,867 @@
-use common::{Piece, PieceLocation};
+use common::{CellGrid, Piece, PieceLocation};
+use eval::{Dellacherie, Evaluator, PlacementInfo};
+use game::Game;
+use movegen::Placement;
+use rules::TopOut;
+use tspin::TSpin;
+
+/// Evaluates `$body` once for every implementation, with `$i` as an alias for it.
+#[cfg(test)]
+macro_rules! for_each_implementation {
+    (|$i:ident| $body:expr) => {{
+        {
+            type $i = $crate::Naive;
+            $body;
+        }
+        {
+            type $i = $crate::NaiveColHeights;
+            $body;
+        }
+        {
+            type $i = $crate::RowBits;
+            $body;
+        }
+        {
+            type $i = $crate::RowBitsColHeights;
+            $body;
+        }
+        {
+            type $i = $crate::ColBits;
+            $body;
+        }
+        {
+            type $i = $crate::ColBitsPext;
+            $body;
+        }
+    }};
+}
 
 pub mod common;
+pub mod eval;
+#[cfg(feature = "fumen")]
+pub mod fumen_io;
+pub mod game;
+pub mod movegen;
+pub mod notation;
+pub mod pc;
+pub mod perft;
+pub mod randomizer;
+pub mod rotation;
+pub mod rules;
+pub mod tspin;
 
-mod naive;
-mod naive_col_heights;
-mod row_bits;
-mod row_bits_col_heights;
-mod column_bits;
-mod column_bits_pext;
+pub mod column_bits;
+pub mod column_bits_pext;
+pub mod naive;
+pub mod naive_col_heights;
+pub mod row_bits;
+pub mod row_bits_col_heights;
 
+pub use column_bits::ColBits;
+pub use column_bits_pext::ColBitsPext;
 pub use naive::Naive;
 pub use naive_col_heights::NaiveColHeights;
 pub use row_bits::RowBits;
 pub use row_bits_col_heights::RowBitsColHeights;
-pub use column_bits::ColBits;
-pub use column_bits_pext::ColBitsPext;
 
 #[cfg(test)]
 #[test]
 fn check_same() {
     use rand::prelude::*;
+    use randomizer::PieceGenerator;
 
-    let mut piece_sequence = vec![];
-    for _ in 0..1000 {
-        piece_sequence.push(match thread_rng().gen_range(0..7) {
-            0 => common::Piece::I,
-            1 => common::Piece::O,
-            2 => common::Piece::T,
-            3 => common::Piece::L,
-            4 => common::Piece::J,
-            5 => common::Piece::S,
-            6 => common::Piece::Z,
-            _ => unreachable!(),
-        })
-    }
-    dbg!(&piece_sequence);
-
-    let results = vec![
-        Naive::simulate(&piece_sequence).fumenize(),
-        NaiveColHeights::simulate(&piece_sequence).fumenize(),
-        RowBits::simulate(&piece_sequence).fumenize(),
-        RowBitsColHeights::simulate(&piece_sequence).fumenize(),
-        ColBits::simulate(&piece_sequence).fumenize(),
-        ColBitsPext::simulate(&piece_sequence).fumenize(),
-    ];
-
-    dbg!(results.iter().map(|f| f.encode()).collect::<Vec<_>>());
+    let seed = dbg!(thread_rng().gen());
+    let piece_sequence = randomizer::SevenBag::new(seed).pieces(1000);
+
+    fn run<I: Implementation>(pieces: &[Piece]) -> (CellGrid, u32) {
+        let (game, _) = I::play(pieces, &<Dellacherie>::default());
+        (game.board.to_cells(), game.score)
+    }
+
+    let mut results = vec![];
+    for_each_implementation!(|I| results.push(run::<I>(&piece_sequence)));
+
+    #[cfg(feature = "fumen")]
+    dbg!(results
+        .iter()
+        .map(|(cells, _)| fumen_io::to_fumen(&naive::Board::from_cells(cells)).map(|f| f.encode()))
+        .collect::<Vec<_>>());
 
     for i in 1..results.len() {
         assert!(results[0] == results[i]);
     }
 }
 
+#[cfg(test)]
+#[test]
+fn check_top_out() {
+    // S and Z pieces alone can never clear the board, so this always tops out
+    let piece_sequence: Vec<_> = [Piece::S, Piece::Z, Piece::S]
+        .iter()
+        .copied()
+        .cycle()
+        .take(1000)
+        .collect();
+
+    let (board, outcome) = Naive::simulate(&piece_sequence);
+    assert!(matches!(outcome, Outcome::GameOver { .. }));
+    assert!((0..10).any(|x| board.column_height(x) > 36));
+
+    fn check<I: Implementation>(pieces: &[Piece], expected: &(CellGrid, Outcome)) {
+        let (board, outcome) = I::simulate(pieces);
+        assert_eq!(&(board.to_cells(), outcome), expected);
+    }
+    let expected = (board.to_cells(), outcome);
+    for_each_implementation!(|I| check::<I>(&piece_sequence, &expected));
+}
+
+#[cfg(test)]
+#[test]
+fn check_try_place() {
+    use common::Rotation;
+
+    fn check<I: Implementation>() {
+        let mut board = I::Board::new();
+        let t = |x, y| PieceLocation {
+            piece: Piece::T,
+            rotation: Rotation::North,
+            x,
+            y,
+        };
+        assert_eq!(board.try_place(t(0, 0)), Err(PlacementError::OutOfBounds));
+        assert_eq!(board.try_place(t(4, 39)), Err(PlacementError::OutOfBounds));
+        assert_eq!(board.try_place(t(4, 1)), Err(PlacementError::Floating));
+        assert_eq!(board.try_place(t(4, 0)), Ok(()));
+        assert_eq!(board.try_place(t(5, 1)), Err(PlacementError::Overlap));
+        assert_eq!(board.try_place(t(4, 2)), Ok(()));
+        assert_eq!(board.try_place(t(4, 38)), Err(PlacementError::Floating));
+    }
+
+    for_each_implementation!(|I| check::<I>());
+}
+
+#[cfg(test)]
+#[test]
+fn check_features_same() {
+    use rand::prelude::*;
+    use randomizer::PieceGenerator;
+
+    fn features<B: Features>(board: &B) -> [i32; 4] {
+        [
+            board.row_transitions(),
+            board.column_transitions(),
+            board.buried_holes(),
+            board.wells(),
+        ]
+    }
+
+    fn check<I: Implementation>(board: &naive::Board, expected: [i32; 4]) {
+        assert_eq!(features(&board.convert::<I::Board>()), expected);
+    }
+
+    let mut pieces = randomizer::Uniform::new(thread_rng().gen());
+    let mut board = naive::Board::new();
+    for _ in 0..1000 {
+        let piece = pieces.next_piece();
+        let placement = match Naive::suggest(&board, piece, &<Dellacherie>::default()) {
+            Some(placement) => placement,
+            None => {
+                board = naive::Board::new();
+                continue;
+            }
+        };
+        board.place(placement);
+
+        let expected = features(&board);
+        for_each_implementation!(|I| check::<I>(&board, expected));
+
+        board.collapse_lines();
+    }
+
+    // boards that are edited directly or get garbage can have empty rows under filled ones
+    let mut rng = thread_rng();
+    for _ in 0..1000 {
+        let mut cells = [[false; 10]; 40];
+        let height = rng.gen_range(0..=40);
+        for row in &mut cells[..height] {
+            if rng.gen_bool(0.8) {
+                for cell in row.iter_mut() {
+                    *cell = rng.gen_bool(0.6);
+                }
+            }
+        }
+        let board = naive::Board::from_cells(&cells);
+        let expected = features(&board);
+        for_each_implementation!(|I| check::<I>(&board, expected));
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn check_weights() {
+    use eval::DellacherieWeights;
+    use rand::prelude::*;
+    use randomizer::PieceGenerator;
+
+    fn check<I: Implementation>(pieces: &[Piece], tuned: &Dellacherie<f64>) -> CellGrid {
+        let (board, outcome) = I::simulate(pieces);
+        let (float_board, float_outcome) = I::simulate_with(pieces, &Dellacherie::<f64>::default());
+        assert_eq!(board.to_cells(), float_board.to_cells());
+        assert_eq!(outcome, float_outcome);
+        I::simulate_with(pieces, tuned).0.to_cells()
+    }
+
+    let piece_sequence = randomizer::Uniform::new(thread_rng().gen()).pieces(500);
+    let tuned = Dellacherie::new(DellacherieWeights {
+        eroded_piece_cells: 1.5,
+        landing_height: -0.5,
+        row_transitions: -3.25,
+        column_transitions: -1.0,
+        buried_holes: -7.5,
+        wells: -3.0,
+    });
+
+    let expected = check::<Naive>(&piece_sequence, &tuned);
+    for_each_implementation!(|I| assert_eq!(check::<I>(&piece_sequence, &tuned), expected));
+}
+
+#[cfg(test)]
+#[test]
+fn check_tie_break() {
+    use eval::TieBreak;
+    use rand::prelude::*;
+    use randomizer::PieceGenerator;
+
+    let piece_sequence = randomizer::Uniform::new(thread_rng().gen()).pieces(1000);
+    let evaluator = TieBreak(<Dellacherie>::default());
+
+    fn simulate<I: Implementation>(
+        pieces: &[Piece],
+        evaluator: &TieBreak<Dellacherie>,
+    ) -> CellGrid {
+        I::simulate_with(pieces, evaluator).0.to_cells()
+    }
+
+    let expected = simulate::<Naive>(&piece_sequence, &evaluator);
+    for_each_implementation!(|I| assert_eq!(simulate::<I>(&piece_sequence, &evaluator), expected));
+
+    // on an empty board O placements against either wall tie, and the right side has priority
+    let o = Naive::suggest(&naive::Board::new(), Piece::O, &evaluator).unwrap();
+    assert_eq!((o.x, o.y), (8, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn check_movegen_same() {
+    use movegen::DropMode;
+    use rand::prelude::*;
+
+    fn check<I: Implementation>(
+        board: &naive::Board,
+        piece: Piece,
+        mode: DropMode,
+        expected: &[Placement],
+    ) {
+        assert_eq!(
+            movegen::find_placements(&board.convert::<I::Board>(), piece, mode),
+            expected
+        );
+    }
+
+    let mut board = naive::Board::new();
+    for _ in 0..100 {
+        for &piece in &Piece::ALL {
+            for &mode in &[DropMode::HardDrop, DropMode::SoftDrop] {
+                let expected = movegen::find_placements(&board, piece, mode);
+                assert!(!expected.is_empty());
+                for_each_implementation!(|I| check::<I>(&board, piece, mode, &expected));
+            }
+        }
+
+        let piece = *Piece::ALL.choose(&mut thread_rng()).unwrap();
+        match Naive::suggest(&board, piece, &<Dellacherie>::default()) {
+            Some(placement) if placement.y < 15 => board.place(placement),
+            _ => board = naive::Board::new(),
+        }
+        board.collapse_lines();
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn check_queries_same() {
+    use rand::prelude::*;
+    use randomizer::PieceGenerator;
+
+    fn check<I: Implementation>(placements: &[PieceLocation]) {
+        let mut reference = naive::Board::new();
+        let mut board = I::Board::new();
+        for &placement in placements {
+            reference.place(placement);
+            board.place(placement);
+            for _ in 0..2 {
+                for y in 0..40 {
+                    for x in 0..10 {
+                        assert_eq!(reference.get(x, y), board.get(x, y));
+                        assert_eq!(reference.corners(x, y), board.corners(x, y));
+                    }
+                    assert_eq!(reference.is_row_full(y), board.is_row_full(y));
+                    assert_eq!(reference.is_row_empty(y), board.is_row_empty(y));
+                }
+                for x in 0..10 {
+                    assert_eq!(reference.column_height(x), board.column_height(x));
+                }
+                assert_eq!(reference.is_empty(), board.is_empty());
+                assert_eq!(reference.collapse_lines(), board.collapse_lines());
+            }
+        }
+    }
+
+    let mut pieces = randomizer::Uniform::new(thread_rng().gen());
+    let mut board = naive::Board::new();
+    let mut placements = vec![];
+    for _ in 0..300 {
+        let piece = pieces.next_piece();
+        let placement = match Naive::suggest(&board, piece, &<Dellacherie>::default()) {
+            Some(placement) => placement,
+            None => break,
+        };
+        board.place(placement);
+        board.collapse_lines();
+        placements.push(placement);
+    }
+
+    for_each_implementation!(|I| check::<I>(&placements));
+}
+
+#[cfg(test)]
+#[test]
+fn check_conversions() {
+    use rand::prelude::*;
+    use std::fmt::Debug;
+
+    fn round_trip<A: Implementation, B: Implementation>(cells: &CellGrid)
+    where
+        A::Board: PartialEq + Debug,
+        B::Board: PartialEq + Debug,
+    {
+        let a = A::Board::from_cells(cells);
+        let b = B::Board::from_cells(cells);
+        assert_eq!(a.to_cells(), *cells);
+        assert_eq!(a.convert::<B::Board>(), b);
+        assert_eq!(b.convert::<A::Board>(), a);
+    }
+
+    let mut cells = [[false; 10]; 40];
+    for row in cells.iter_mut().take(20) {
+        for cell in row.iter_mut() {
+            *cell = thread_rng().gen_bool(0.6);
+        }
+    }
+
+    for_each_implementation!(|A| for_each_implementation!(|B| round_trip::<A, B>(&cells)));
+}
+
+#[cfg(test)]
+#[test]
+fn check_hold() {
+    fn check<I: Implementation>() {
+        let board = I::Board::from_cells(
+            &"
+                #########.
+                #########.
+                #########.
+                #########.
+            "
+            .parse::<naive::Board>()
+            .unwrap()
+            .to_cells(),
+        );
+        let mv = I::suggest_with_hold(
+            &board,
+            Piece::S,
+            None,
+            Some(Piece::I),
+            &<Dellacherie>::default(),
+        )
+        .unwrap();
+        assert!(mv.hold);
+        assert_eq!(mv.location.piece, Piece::I);
+
+        // the held piece is used instead of the next one
+        let mv = I::suggest_with_hold(
+            &board,
+            Piece::S,
+            Some(Piece::I),
+            Some(Piece::Z),
+            &<Dellacherie>::default(),
+        )
+        .unwrap();
+        assert_eq!(mv.location.piece, Piece::I);
+
+        let mv = I::suggest_with_hold(
+            &board,
+            Piece::I,
+            Some(Piece::S),
+            None,
+            &<Dellacherie>::default(),
+        )
+        .unwrap();
+        assert!(!mv.hold);
+    }
+
+    for_each_implementation!(|I| check::<I>());
+}
+
+#[cfg(test)]
+#[test]
+fn check_lookahead() {
+    use rand::prelude::*;
+    use randomizer::PieceGenerator;
+
+    fn check<I: Implementation>(
+        cells: &CellGrid,
+        queue: &[Piece],
+        expected: Option<PieceLocation>,
+    ) {
+        let board = I::Board::from_cells(cells);
+        let evaluator = <Dellacherie>::default();
+        assert_eq!(I::suggest_lookahead(&board, queue, &evaluator), expected);
+        assert_eq!(
+            I::suggest_lookahead(&board, &queue[..1], &evaluator),
+            I::suggest(&board, queue[0], &evaluator)
+        );
+    }
+
+    let mut pieces = randomizer::Uniform::new(thread_rng().gen());
+    let mut board = naive::Board::new();
+    for _ in 0..20 {
+        let queue = pieces.pieces(2);
+        let expected = Naive::suggest_lookahead(&board, &queue, &<Dellacherie>::default());
+        let cells = board.to_cells();
+        for_each_implementation!(|I| check::<I>(&cells, &queue, expected));
+
+        board.place(expected.unwrap());
+        board.collapse_lines();
+    }
+    assert_eq!(
+        Naive::suggest_lookahead(&board, &[], &<Dellacherie>::default()),
+        None
+    );
+
+    // the I fits but the O never does, so the I is placed as if there were no preview
+    let mut cells = [[false; 10]; 40];
+    for row in &mut cells[..39] {
+        *row = [
+            true, false, true, false, true, false, true, false, true, false,
+        ];
+    }
+    let expected = Naive::suggest(
+        &naive::Board::from_cells(&cells),
+        Piece::I,
+        &<Dellacherie>::default(),
+    );
+    assert!(expected.is_some());
+    for_each_implementation!(|I| check::<I>(&cells, &[Piece::I, Piece::O], expected));
+}
+
+#[cfg(test)]
+#[test]
+fn check_perft_same() {
+    fn check<I: Implementation>(cells: &CellGrid, queue: &[Piece], expected: u64) {
+        assert_eq!(
+            perft::perft::<I>(&I::Board::from_cells(cells), queue),
+            expected
+        );
+    }
+
+    let board: naive::Board = "
+        ....##....
+        ##......##
+        ##......##
+    "
+    .parse()
+    .unwrap();
+    let queue = [Piece::T, Piece::S, Piece::I];
+    let expected = perft::perft::<Naive>(&board, &queue);
+    let cells = board.to_cells();
+    for_each_implementation!(|I| check::<I>(&cells, &queue, expected));
+}
+
+#[cfg(test)]
+#[test]
+fn check_garbage() {
+    use rand::prelude::*;
+
+    fn check<I: Implementation>(
+        cells: &CellGrid,
+        rows: u32,
+        holes: u16,
+        expected: &CellGrid,
+        piece: Piece,
+    ) {
+        let mut board = I::Board::from_cells(cells);
+        let topped_out = cells[40 - rows as usize..]
+            .iter()
+            .any(|row| row.contains(&true));
+        assert_eq!(board.add_garbage_holes(rows, holes).is_err(), topped_out);
+        assert_eq!(&board.to_cells(), expected);
+        let reference = naive::Board::from_cells(expected);
+        for x in 0..10 {
+            assert_eq!(board.column_height(x), reference.column_height(x));
+        }
+
+        // the evaluation has to see the same board too, empty garbage rows included
+        assert_eq!(board.row_transitions(), reference.row_transitions());
+        assert_eq!(board.column_transitions(), reference.column_transitions());
+        assert_eq!(board.buried_holes(), reference.buried_holes());
+        assert_eq!(board.wells(), reference.wells());
+        assert_eq!(
+            I::suggest(&board, piece, &<Dellacherie>::default()),
+            Naive::suggest(&reference, piece, &<Dellacherie>::default())
+        );
+
+        // garbage rows can be full or empty, and everything above them still has to collapse
+        let mut collapsed = [[false; 10]; 40];
+        let remaining: Vec<_> = expected.iter().filter(|row| **row != [true; 10]).collect();
+        for (row, &&remaining) in collapsed.iter_mut().zip(&remaining) {
+            *row = remaining;
+        }
+        assert_eq!(board.collapse_lines(), 40 - remaining.len() as i32);
+        assert_eq!(board.to_cells(), collapsed);
+        let reference = naive::Board::from_cells(&collapsed);
+        for x in 0..10 {
+            assert_eq!(board.column_height(x), reference.column_height(x));
+        }
+    }
+
+    let mut rng = thread_rng();
+    for _ in 0..200 {
+        let mut cells = [[false; 10]; 40];
+        let height = rng.gen_range(0..=40);
+        for row in &mut cells[..height] {
+            for cell in row.iter_mut() {
+                *cell = rng.gen_bool(0.6);
+            }
+        }
+        let rows = *[0, 1, 2, 4, 9, 40].choose(&mut rng).unwrap();
+        let holes = match rng.gen_range(0..4) {
+            0 => 0,
+            1 => (1 << 10) - 1,
+            _ => rng.gen_range(0..1 << 10),
+        };
+
+        let mut expected = [[false; 10]; 40];
+        for (y, row) in expected.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = match y.checked_sub(rows as usize) {
+                    Some(y) => cells[y][x],
+                    None => holes & 1 << x == 0,
+                };
+            }
+        }
+
+        let piece = *Piece::ALL.choose(&mut rng).unwrap();
+        for_each_implementation!(|I| check::<I>(&cells, rows, holes, &expected, piece));
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn check_pc_same() {
+    fn check<I: Implementation>(
+        cells: &CellGrid,
+        queue: &[Piece],
+        expected: &[Vec<PieceLocation>],
+    ) {
+        assert_eq!(
+            pc::solve::<I>(&I::Board::from_cells(cells), queue, None, true, 2),
+            expected
+        );
+    }
+
+    let board: naive::Board = "
+        ##......##
+    "
+    .parse()
+    .unwrap();
+    let queue = [Piece::L, Piece::J, Piece::O, Piece::O, Piece::T];
+    let expected = pc::solve::<Naive>(&board, &queue, None, true, 2);
+    assert!(!expected.is_empty());
+    let cells = board.to_cells();
+    for_each_implementation!(|I| check::<I>(&cells, &queue, &expected));
+}
+
 pub trait Implementation {
-    type Board: BoardImpl;
+    type Board: BoardImpl + Features;
 
     const NAME: &'static str;
 
-    fn suggest(board: &Self::Board, piece: Piece) -> Option<PieceLocation>;
+    /// Calls `f` with the resulting board for each hard drop placement of the piece.
+    fn for_each_placement(
+        board: &Self::Board,
+        piece: Piece,
+        f: impl FnMut(&Self::Board, &PlacementInfo),
+    );
+
+    fn suggest<E: Evaluator<Self::Board>>(
+        board: &Self::Board,
+        piece: Piece,
+        evaluator: &E,
+    ) -> Option<PieceLocation> {
+        Self::suggest_with_score(board, piece, evaluator).map(|(p, _)| p)
+    }
+
+    /// Like `suggest`, but also returns the score of the placement.
+    fn suggest_with_score<E: Evaluator<Self::Board>>(
+        board: &Self::Board,
+        piece: Piece,
+        evaluator: &E,
+    ) -> Option<(PieceLocation, E::Score)> {
+        let mut best = None;
+        Self::for_each_placement(board, piece, |board, placement| {
+            let score = evaluator.evaluate(board, placement);
+            match best {
+                None => best = Some((placement.location, score)),
+                Some((_, s)) => {
+                    if score > s {
+                        best = Some((placement.location, score))
+                    }
+                }
+            }
+        });
+        best
+    }
+
+    /// Chooses between placing `current` and holding it to place the held piece instead, or the
+    /// `next` piece if the hold slot is empty. Not holding is preferred when the scores are equal.
+    fn suggest_with_hold<E: Evaluator<Self::Board>>(
+        board: &Self::Board,
+        current: Piece,
+        hold: Option<Piece>,
+        next: Option<Piece>,
+        evaluator: &E,
+    ) -> Option<Move> {
+        let best = Self::suggest_with_score(board, current, evaluator);
+        let held = match hold.or(next) {
+            Some(piece) if piece != current => Self::suggest_with_score(board, piece, evaluator),
+            _ => None,
+        };
+        match (best, held) {
+            (Some((_, score)), Some((location, held_score))) if held_score > score => Some(Move {
+                hold: true,
+                location,
+            }),
+            (None, Some((location, _))) => Some(Move {
+                hold: true,
+                location,
+            }),
+            (best, _) => best.map(|(location, _)| Move {
+                hold: false,
+                location,
+            }),
+        }
+    }
+
+    /// Chooses a placement for the first piece of `queue` by searching every sequence of
+    /// placements of the queue and scoring the board after the last one. If no sequence places
+    /// the whole queue, the ones that place the most pieces are scored instead.
+    fn suggest_lookahead<E: Evaluator<Self::Board>>(
+        board: &Self::Board,
+        queue: &[Piece],
+        evaluator: &E,
+    ) -> Option<PieceLocation> {
+        lookahead::<Self, E>(board, queue, evaluator).map(|(p, _)| p)
+    }
+
+    fn simulate(pieces: &[Piece]) -> (Self::Board, Outcome) {
+        Self::simulate_with(pieces, &<Dellacherie>::default())
+    }
 
-    fn simulate(pieces: &[Piece]) -> Self::Board {
-        let mut board = Self::Board::new();
-        for &p in pieces {
-            if let Some(placement) = Self::suggest(&board, p) {
-                board.place(placement);
-                board.collapse_lines();
+    /// Places pieces until the sequence runs out or a piece has no legal placement. Each piece
+    /// can be held, and a piece left in the hold slot at the end is not placed.
+    fn simulate_with<E: Evaluator<Self::Board>>(
+        pieces: &[Piece],
+        evaluator: &E,
+    ) -> (Self::Board, Outcome) {
+        let (game, outcome) = Self::play(pieces, evaluator);
+        (game.board, outcome)
+    }
+
+    /// Like `simulate_with`, but keeps the score of the game.
+    fn play<E: Evaluator<Self::Board>>(
+        pieces: &[Piece],
+        evaluator: &E,
+    ) -> (Game<Self::Board>, Outcome) {
+        let mut game = Game::new();
+        let mut hold = None;
+        let mut queue = pieces.iter().copied().peekable();
+        let mut pieces_placed = 0;
+        while let Some(current) = queue.next() {
+            let next = queue.peek().copied();
+            let mv = match Self::suggest_with_hold(&game.board, current, hold, next, evaluator) {
+                Some(mv) => mv,
+                None => return (game, Outcome::GameOver { pieces_placed }),
+            };
+            // placements are hard drops, so they are never T-spins
+            let placement = Placement {
+                location: mv.location,
+                tspin: TSpin::None,
+            };
+            if game.lock(placement).is_err() {
+                return (game, Outcome::GameOver { pieces_placed });
+            }
+            if mv.hold {
+                if hold.is_none() {
+                    queue.next();
+                }
+                hold = Some(current);
             }
+            pieces_placed += 1;
         }
-        board
+        (game, Outcome::Completed)
     }
 }
 
+/// The best placement of the first piece of `queue` along with the best score reachable from it.
+fn lookahead<I: Implementation + ?Sized, E: Evaluator<I::Board>>(
+    board: &I::Board,
+    queue: &[Piece],
+    evaluator: &E,
+) -> Option<(PieceLocation, (usize, E::Score))> {
+    let (&piece, rest) = queue.split_first()?;
+    let mut best: Option<(PieceLocation, (usize, E::Score))> = None;
+    I::for_each_placement(board, piece, |board, placement| {
+        // sequences that place more of the queue win, so a piece that can be placed is always
+        // placed even if the ones after it top out
+        let score = match lookahead::<I, E>(board, rest, evaluator) {
+            Some((_, (placed, score))) => (placed + 1, score),
+            None => (1, evaluator.evaluate(board, placement)),
+        };
+        if best.is_none_or(|(_, s)| score > s) {
+            best = Some((placement.location, score));
+        }
+    });
+    best
+}
+
+/// A placement, possibly made after holding the current piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub hold: bool,
+    pub location: PieceLocation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Completed,
+    GameOver { pieces_placed: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlacementError {
+    /// A cell of the piece is outside the 10x40 board.
+    OutOfBounds,
+    /// A cell of the piece is already filled.
+    Overlap,
+    /// No cell of the piece rests on the floor or a filled cell.
+    Floating,
+}
+
+impl std::fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PlacementError::OutOfBounds => write!(f, "piece is out of bounds"),
+            PlacementError::Overlap => write!(f, "piece overlaps filled cells"),
+            PlacementError::Floating => write!(f, "piece is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
 pub trait BoardImpl {
     fn new() -> Self;
     fn place(&mut self, placement: PieceLocation);
     fn collapse_lines(&mut self) -> i32;
+
+    /// Places the piece after checking that it is on the board, does not overlap filled cells,
+    /// and is supported. The board is unchanged if the placement is rejected.
+    fn try_place(&mut self, placement: PieceLocation) -> Result<(), PlacementError> {
+        let cells = placement.cells();
+        if cells
+            .iter()
+            .any(|&(x, y)| !(0..10).contains(&x) || !(0..40).contains(&y))
+        {
+            return Err(PlacementError::OutOfBounds);
+        }
+        if cells.iter().any(|&(x, y)| self.get(x, y)) {
+            return Err(PlacementError::Overlap);
+        }
+        if !cells.iter().any(|&(x, y)| y == 0 || self.get(x, y - 1)) {
+            return Err(PlacementError::Floating);
+        }
+        self.place(placement);
+        Ok(())
+    }
+
+    /// Whether the cell at `(x, y)` is filled. Both coordinates must be on the board.
+    fn get(&self, x: i8, y: i8) -> bool;
+    /// One more than the y coordinate of the highest filled cell in column `x`, or 0 if the
+    /// column is empty.
+    fn column_height(&self, x: i8) -> i8;
+    fn is_row_full(&self, y: i8) -> bool;
+    fn is_row_empty(&self, y: i8) -> bool;
+    fn is_empty(&self) -> bool;
+    /// A mask of `CORNER_*` bits for the diagonal neighbours of `(x, y)` that are filled. The
+    /// walls and floor count as filled and the area above the board counts as empty.
+    fn corners(&self, x: i8, y: i8) -> u8;
+
+    /// Pushes the stack up by `rows` and fills the new bottom rows, except for the columns set in
+    /// `holes`. Returns `TopOut::GarbageOut` if filled cells were pushed past the top of the
+    /// board, which are lost.
+    fn add_garbage_holes(&mut self, rows: u32, holes: u16) -> Result<(), TopOut>;
+
+    /// Like `add_garbage_holes`, with one hole per row. Panics if `hole_column` isn't a column
+    /// of the board.
+    fn add_garbage(&mut self, rows: u32, hole_column: i8) -> Result<(), TopOut> {
+        assert!((0..10).contains(&hole_column), "no column {}", hole_column);
+        self.add_garbage_holes(rows, 1 << hole_column)
+    }
+
+    fn from_cells(cells: &CellGrid) -> Self;
+
+    fn to_cells(&self) -> CellGrid {
+        let mut cells = [[false; 10]; 40];
+        for (y, row) in cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = self.get(x as i8, y as i8);
+            }
+        }
+        cells
+    }
+
+    /// Converts this board to another representation.
+    fn convert<B: BoardImpl>(&self) -> B
+    where
+        Self: Sized,
+    {
+        B::from_cells(&self.to_cells())
+    }
+}
+
+/// The board features used by Dellacherie's evaluation function. The floor and walls count as
+/// filled and the area above the 40 rows of the board counts as empty.
+pub trait Features {
+    /// The number of horizontally adjacent pairs of cells where one is filled and the other is
+    /// empty, including pairs with the walls.
+    fn row_transitions(&self) -> i32;
+    /// The number of vertically adjacent pairs of cells where one is filled and the other is
+    /// empty, including pairs with the floor and the empty area above the board.
+    fn column_transitions(&self) -> i32;
+    /// The number of empty cells with a filled cell somewhere above them.
+    fn buried_holes(&self) -> i32;
+    /// For each empty cell with filled cells or walls on both sides, the number of empty cells
+    /// from it down to the next filled cell or the floor, summed.
+    fn wells(&self) -> i32;
 }
@@ -0,0 +1,354 @@
+//! Move generation, using the Super Rotation System unless another rotation system is given.
+//!
+//! Pieces start at the guideline spawn position and can be shifted and rotated, with wall kicks.
+//! The area above the 40 rows of the board is empty, but a piece can't lock with any cell up
+//! there.
+
+use crate::common::*;
+use crate::rotation::{RotationSystem, Srs};
+use crate::tspin::{self, TSpin};
+use crate::BoardImpl;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DropMode {
+    /// The piece is shifted and rotated at spawn height and then hard dropped. Once it has landed
+    /// it can still be rotated, and if a kick moves it off the ground it drops again.
+    HardDrop,
+    /// The piece can be shifted, rotated and soft dropped one row at a time in any order, so it
+    /// can be tucked or slid under overhangs.
+    SoftDrop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Placement {
+    pub location: PieceLocation,
+    /// The best T-spin any path to this position gets.
+    pub tspin: TSpin,
+}
+
+/// Every placement the piece can lock in, each reported once by its canonical location.
+pub fn find_placements<B: BoardImpl>(board: &B, piece: Piece, mode: DropMode) -> Vec<Placement> {
+    let spawn = PieceLocation {
+        piece,
+        rotation: Rotation::North,
+        x: SPAWN_X,
+        y: SPAWN_Y,
+    };
+    find_placements_from(&Srs, board, spawn, mode, false)
+}
+
+/// Like `find_placements`, but starting from the given location, which uses the shapes of the
+/// rotation system, and optionally allowing 180 degree rotations. The placements are still
+/// reported as canonical `PieceLocation`s.
+pub fn find_placements_from<R: RotationSystem, B: BoardImpl>(
+    system: &R,
+    board: &B,
+    spawn: PieceLocation,
+    mode: DropMode,
+    allow_180: bool,
+) -> Vec<Placement> {
+    if system.collides(board, spawn) {
+        return vec![];
+    }
+
+    let mut spins = Spins::default();
+    let locations = match mode {
+        DropMode::HardDrop => hard_drop_placements(system, board, spawn, allow_180, &mut spins),
+        DropMode::SoftDrop => soft_drop_placements(system, board, spawn, allow_180, &mut spins),
+    };
+    let mut placements: Vec<Placement> = vec![];
+    let mut seen: HashMap<PieceLocation, usize> = HashMap::new();
+    for location in locations {
+        let tspin = spins.get(&location).copied().unwrap_or(TSpin::None);
+        let canonical = system.normalize(location);
+        match seen.get(&canonical) {
+            Some(&index) => {
+                let placement = &mut placements[index];
+                placement.tspin = placement.tspin.max(tspin);
+            }
+            None => {
+                seen.insert(canonical, placements.len());
+                placements.push(Placement {
+                    location: canonical,
+                    tspin,
+                });
+            }
+        }
+    }
+    placements
+}
+
+/// The best T-spin found for each position that was locked right after a rotation.
+type Spins = HashMap<PieceLocation, TSpin>;
+
+/// Records the T-spin for a rotation that ended with the piece on the ground.
+fn record_spin<R: RotationSystem, B: BoardImpl>(
+    system: &R,
+    board: &B,
+    spins: &mut Spins,
+    from: Rotation,
+    location: PieceLocation,
+    kick: usize,
+) {
+    let down = PieceLocation {
+        y: location.y - 1,
+        ..location
+    };
+    if !system.collides(board, down) {
+        return;
+    }
+    let tspin = tspin::classify_with(system, board, location, Some((from, kick)));
+    if tspin != TSpin::None {
+        let best = spins.entry(location).or_insert(tspin);
+        *best = (*best).max(tspin);
+    }
+}
+
+/// The states a piece can rotate to.
+fn rotations(from: Rotation, allow_180: bool) -> impl Iterator<Item = Rotation> {
+    let count = if allow_180 { 3 } else { 2 };
+    IntoIterator::into_iter([from.cw(), from.ccw(), from.flip()]).take(count)
+}
+
+fn hard_drop_placements<R: RotationSystem, B: BoardImpl>(
+    system: &R,
+    board: &B,
+    spawn: PieceLocation,
+    allow_180: bool,
+    spins: &mut Spins,
+) -> Vec<PieceLocation> {
+    let mut airborne = Visited::new();
+    let mut queue = vec![spawn];
+    airborne.insert(spawn);
+
+    let mut landed = Visited::new();
+    let mut landed_queue = vec![];
+    let mut placements = vec![];
+
+    while let Some(location) = queue.pop() {
+        for &dx in &[-1, 1] {
+            let shifted = PieceLocation {
+                x: location.x + dx,
+                ..location
+            };
+            if !system.collides(board, shifted) && airborne.insert(shifted) {
+                queue.push(shifted);
+            }
+        }
+        for to in rotations(location.rotation, allow_180) {
+            if let Some((rotated, kick)) = system.rotate(board, location, to) {
+                record_spin(system, board, spins, location.rotation, rotated, kick);
+                if airborne.insert(rotated) {
+                    queue.push(rotated);
+                }
+            }
+        }
+
+        let dropped = sonic_drop(system, board, location);
+        if landed.insert(dropped) {
+            landed_queue.push(dropped);
+        }
+    }
+
+    while let Some(location) = landed_queue.pop() {
+        if system.location_cells(location).iter().all(|&(_, y)| y < 40) {
+            placements.push(location);
+        }
+        for to in rotations(location.rotation, allow_180) {
+            if let Some((rotated, kick)) = system.rotate(board, location, to) {
+                record_spin(system, board, spins, location.rotation, rotated, kick);
+                let dropped = sonic_drop(system, board, rotated);
+                if landed.insert(dropped) {
+                    landed_queue.push(dropped);
+                }
+            }
+        }
+    }
+
+    placements
+}
+
+fn soft_drop_placements<R: RotationSystem, B: BoardImpl>(
+    system: &R,
+    board: &B,
+    spawn: PieceLocation,
+    allow_180: bool,
+    spins: &mut Spins,
+) -> Vec<PieceLocation> {
+    let mut visited = Visited::new();
+    let mut queue = vec![spawn];
+    visited.insert(spawn);
+    let mut placements = vec![];
+
+    while let Some(location) = queue.pop() {
+        let down = PieceLocation {
+            y: location.y - 1,
+            ..location
+        };
+        if system.collides(board, down) {
+            if system.location_cells(location).iter().all(|&(_, y)| y < 40) {
+                placements.push(location);
+            }
+        } else if visited.insert(down) {
+            queue.push(down);
+        }
+
+        for &dx in &[-1, 1] {
+            let shifted = PieceLocation {
+                x: location.x + dx,
+                ..location
+            };
+            if !system.collides(board, shifted) && visited.insert(shifted) {
+                queue.push(shifted);
+            }
+        }
+        for to in rotations(location.rotation, allow_180) {
+            if let Some((rotated, kick)) = system.rotate(board, location, to) {
+                record_spin(system, board, spins, location.rotation, rotated, kick);
+                if visited.insert(rotated) {
+                    queue.push(rotated);
+                }
+            }
+        }
+    }
+
+    placements
+}
+
+/// Moves the piece down until it rests on something.
+fn sonic_drop<R: RotationSystem, B: BoardImpl>(
+    system: &R,
+    board: &B,
+    mut location: PieceLocation,
+) -> PieceLocation {
+    location.y -= 1;
+    while !system.collides(board, location) {
+        location.y -= 1;
+    }
+    location.y += 1;
+    location
+}
+
+/// A set of piece positions with the same piece, as one bit per x coordinate for each rotation
+/// and y coordinate.
+struct Visited {
+    bits: [[u16; Visited::HEIGHT]; 4],
+}
+
+impl Visited {
+    const HEIGHT: usize = 48;
+    const X_OFFSET: i8 = 3;
+    const Y_OFFSET: i8 = 4;
+
+    fn new() -> Self {
+        Visited {
+            bits: [[0; Visited::HEIGHT]; 4],
+        }
+    }
+
+    /// Returns whether the position was newly inserted. Positions too far off the board are
+    /// never inserted.
+    fn insert(&mut self, location: PieceLocation) -> bool {
+        let x = location.x + Visited::X_OFFSET;
+        let y = location.y + Visited::Y_OFFSET;
+        if !(0..16).contains(&x) || !(0..Visited::HEIGHT as i8).contains(&y) {
+            return false;
+        }
+        let row = &mut self.bits[location.rotation as usize][y as usize];
+        let new = *row & 1 << x == 0;
+        *row |= 1 << x;
+        new
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn tuck_by_kick() {
+    use crate::naive;
+
+    // A T can only get under the overhang on the left by rotating into the slot.
+    let board: naive::Board = "
+        ###.......
+        ##...#####
+        ###.######
+    "
+    .parse()
+    .unwrap();
+    let tsd = PieceLocation {
+        piece: Piece::T,
+        rotation: Rotation::South,
+        x: 3,
+        y: 1,
+    };
+    let tsd = Placement {
+        location: tsd,
+        tspin: TSpin::Full,
+    };
+    assert!(find_placements(&board, Piece::T, DropMode::HardDrop).contains(&tsd));
+    assert!(find_placements(&board, Piece::T, DropMode::SoftDrop).contains(&tsd));
+}
+
+#[cfg(test)]
+#[test]
+fn slide_under_overhang() {
+    use crate::naive;
+
+    let board: naive::Board = "
+        #####.....
+        #........#
+    "
+    .parse()
+    .unwrap();
+    let slide = PieceLocation {
+        piece: Piece::I,
+        rotation: Rotation::North,
+        x: 2,
+        y: 0,
+    };
+    let slide = Placement {
+        location: slide,
+        tspin: TSpin::None,
+    };
+    assert!(!find_placements(&board, Piece::I, DropMode::HardDrop).contains(&slide));
+    assert!(find_placements(&board, Piece::I, DropMode::SoftDrop).contains(&slide));
+}
+
+#[cfg(test)]
+#[test]
+fn distinct_placements() {
+    use crate::rotation::{Ars, Nes};
+    use crate::{naive, perft::perft, BoardImpl, Naive};
+
+    // On an empty board every placement can be hard dropped, so both modes should find exactly
+    // the ones `for_each_placement` does.
+    let board = naive::Board::new();
+    for &piece in &Piece::ALL {
+        let expected = perft::<Naive>(&board, &[piece]) as usize;
+        assert_eq!(
+            find_placements(&board, piece, DropMode::HardDrop).len(),
+            expected
+        );
+        assert_eq!(
+            find_placements(&board, piece, DropMode::SoftDrop).len(),
+            expected
+        );
+
+        let spawn = PieceLocation {
+            piece,
+            rotation: Rotation::North,
+            x: SPAWN_X,
+            y: SPAWN_Y,
+        };
+        for &mode in &[DropMode::HardDrop, DropMode::SoftDrop] {
+            assert_eq!(
+                find_placements_from(&Ars, &board, spawn, mode, false).len(),
+                expected
+            );
+            assert_eq!(
+                find_placements_from(&Nes, &board, spawn, mode, false).len(),
+                expected
+            );
+        }
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{common::*, BoardImpl, Implementation};
+use crate::{
+    common::*,
+    eval::PlacementInfo,
+    notation::{self, ParseBoardError},
+    rules::TopOut,
+    BoardImpl, Features, Implementation,
+};
+use std::fmt;
+use std::str::FromStr;
 
 pub struct Naive;
 
@@ -26,9 +34,6 @@ impl BoardImpl for Board {
             if self.cells[i] == [true; 10] {
                 continue;
             }
-            if self.cells[current] == [false; 10] {
-                return (i - current) as i32;
-            }
             self.cells[current] = self.cells[i];
             current += 1;
         }
@@ -37,25 +42,95 @@ impl BoardImpl for Board {
         }
         40 - current as i32
     }
-}
 
-impl Board {
     fn get(&self, x: i8, y: i8) -> bool {
         self.cells[y as usize][x as usize]
     }
 
-    #[cfg(test)]
-    pub fn fumenize(self) -> fumen::Fumen {
-        let mut fumen = fumen::Fumen::default();
-        let page = fumen.add_page();
-        for y in 0..23 {
-            for x in 0..10 {
-                if self.get(x as i8, y as i8) {
-                    page.field[y][x] = fumen::CellColor::Grey;
-                }
-            }
+    fn column_height(&self, x: i8) -> i8 {
+        (0..40).rev().find(|&y| self.get(x, y)).map_or(0, |y| y + 1)
+    }
+
+    fn is_row_full(&self, y: i8) -> bool {
+        self.cells[y as usize] == [true; 10]
+    }
+
+    fn is_row_empty(&self, y: i8) -> bool {
+        self.cells[y as usize] == [false; 10]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cells.iter().all(|row| *row == [false; 10])
+    }
+
+    fn corners(&self, x: i8, y: i8) -> u8 {
+        let filled = |x: i8, y: i8| !(0..10).contains(&x) || y < 0 || (y < 40 && self.get(x, y));
+        let mut corners = 0;
+        if filled(x - 1, y + 1) {
+            corners |= CORNER_NW;
+        }
+        if filled(x + 1, y + 1) {
+            corners |= CORNER_NE;
         }
-        fumen
+        if filled(x - 1, y - 1) {
+            corners |= CORNER_SW;
+        }
+        if filled(x + 1, y - 1) {
+            corners |= CORNER_SE;
+        }
+        corners
+    }
+
+    fn add_garbage_holes(&mut self, rows: u32, holes: u16) -> Result<(), TopOut> {
+        let rows = rows.min(40) as usize;
+        let topped_out = self.cells[40 - rows..]
+            .iter()
+            .any(|row| *row != [false; 10]);
+        self.cells.copy_within(..40 - rows, rows);
+        let mut garbage = [true; 10];
+        for (x, cell) in garbage.iter_mut().enumerate() {
+            *cell = holes & 1 << x == 0;
+        }
+        for row in &mut self.cells[..rows] {
+            *row = garbage;
+        }
+        if topped_out {
+            Err(TopOut::GarbageOut)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn from_cells(cells: &CellGrid) -> Self {
+        Board { cells: *cells }
+    }
+
+    fn to_cells(&self) -> CellGrid {
+        self.cells
+    }
+}
+
+impl Board {
+    /// The number of rows up to the highest filled cell, which can have empty rows below it.
+    fn highest(&self) -> i8 {
+        self.cells
+            .iter()
+            .rposition(|row| *row != [false; 10])
+            .map_or(0, |y| y as i8 + 1)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        notation::write_board(self, f)
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, ParseBoardError> {
+        notation::parse_board(s)
     }
 }
 
@@ -64,16 +139,15 @@ impl Implementation for Naive {
 
     const NAME: &'static str = "naive";
 
-    fn suggest(board: &Board, piece: Piece) -> Option<PieceLocation> {
-        let mut best = None;
-
+    fn for_each_placement(board: &Board, piece: Piece, mut f: impl FnMut(&Board, &PlacementInfo)) {
         for &rotation in piece.sensible_rotations() {
             for x in 0..10 {
                 let mut piece = PieceLocation {
                     piece,
                     rotation,
                     x,
-                    y: 37,
+                    // high enough that every cell starts above the board
+                    y: 42,
                 };
 
                 if blocked(board, piece) {
@@ -85,6 +159,10 @@ impl Implementation for Naive {
                 }
                 piece.y += 1;
 
+                if piece.cells().iter().any(|&(_, y)| y >= 40) {
+                    continue;
+                }
+
                 let mut board = *board;
                 board.place(piece);
 
@@ -96,128 +174,95 @@ impl Implementation for Naive {
 
                 let lines_cleared = board.collapse_lines();
 
-                let mut low = 40;
-                let mut high = 0;
-                for &(_, y) in &piece.cells() {
-                    low = low.min(y);
-                    high = high.max(y);
-                }
-
-                let landing_height = low as i32 + high as i32;
-                let eroded_piece_cells_metric = lines_cleared * piece_cells_eliminated;
-                let row_transitions = row_transitions(&board);
-                let column_transitions = column_transitions(&board);
-                let buried_holes = buried_holes(&board);
-                let wells = wells(&board);
-
-                let score = 2 * eroded_piece_cells_metric
-                    - landing_height
-                    - 2 * row_transitions
-                    - 2 * column_transitions
-                    - 8 * buried_holes
-                    - 2 * wells;
-
-                match best {
-                    None => best = Some((piece, score)),
-                    Some((_, s)) => {
-                        if score > s {
-                            best = Some((piece, score))
-                        }
-                    }
-                }
+                f(
+                    &board,
+                    &PlacementInfo {
+                        location: piece,
+                        lines_cleared,
+                        piece_cells_eliminated,
+                        perfect_clear: lines_cleared > 0 && board.is_empty(),
+                    },
+                );
             }
         }
-
-        best.map(|(p, _)| p)
     }
 }
 
 fn blocked(board: &Board, piece: PieceLocation) -> bool {
     for &(x, y) in &piece.cells() {
-        if x < 0 || x >= 10 || y < 0 || y >= 40 {
+        if !(0..10).contains(&x) || y < 0 {
             return true;
         }
-        if board.get(x, y) {
+        if y < 40 && board.get(x, y) {
             return true;
         }
     }
     false
 }
 
-fn row_transitions(board: &Board) -> i32 {
-    let mut count = 0;
-    for (y, row) in board.cells.iter().enumerate() {
-        if *row == [false; 10] {
-            count += (40 - y as i32) * 2;
-            break;
-        }
-        let mut previous = true;
-        for &cell in row {
-            if cell != previous {
+impl Features for Board {
+    fn row_transitions(&self) -> i32 {
+        let highest = self.highest();
+        let mut count = (40 - highest as i32) * 2;
+        for row in &self.cells[..highest as usize] {
+            let mut previous = true;
+            for &cell in row {
+                if cell != previous {
+                    count += 1;
+                }
+                previous = cell;
+            }
+            if !previous {
                 count += 1;
             }
-            previous = cell;
-        }
-        if !previous {
-            count += 1;
         }
+        count
     }
-    count
-}
 
-fn column_transitions(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut previous = [true; 10];
-    for row in &board.cells {
-        count += (0..10).filter(|&x| row[x] != previous[x]).count();
-        previous = *row;
-        if *row == [false; 10] {
-            break;
+    fn column_transitions(&self) -> i32 {
+        let mut count = 0;
+        let mut previous = [true; 10];
+        let stack = &self.cells[..self.highest() as usize];
+        for row in stack.iter().chain(std::iter::once(&[false; 10])) {
+            count += (0..10).filter(|&x| row[x] != previous[x]).count();
+            previous = *row;
         }
+        count as i32
     }
-    count as i32
-}
 
-fn buried_holes(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut depths = [0; 10];
-    for row in board.cells.iter() {
-        for x in 0..10 {
-            if row[x] {
-                count += depths[x];
-                depths[x] = 0;
-            } else {
-                depths[x] += 1;
+    fn buried_holes(&self) -> i32 {
+        let mut count = 0;
+        let mut depths = [0; 10];
+        for row in &self.cells[..self.highest() as usize] {
+            for x in 0..10 {
+                if row[x] {
+                    count += depths[x];
+                    depths[x] = 0;
+                } else {
+                    depths[x] += 1;
+                }
             }
         }
-        if *row == [false; 10] {
-            break;
-        }
+        count
     }
-    count
-}
 
-fn wells(board: &Board) -> i32 {
-    let mut score = 0;
-    let mut depths = [0; 10];
-    for y in 0..40 {
-        let mut all_empty = true;
-        for x in 0..10 {
-            if board.get(x, y) {
-                depths[x as usize] = 0;
-                all_empty = false;
-            } else {
-                depths[x as usize] += 1;
-                let left = x == 0 || board.get(x - 1, y);
-                let right = x == 9 || board.get(x + 1, y);
-                if left && right {
-                    score += depths[x as usize];
+    fn wells(&self) -> i32 {
+        let mut score = 0;
+        let mut depths = [0; 10];
+        for y in 0..self.highest() {
+            for x in 0..10 {
+                if self.get(x, y) {
+                    depths[x as usize] = 0;
+                } else {
+                    depths[x as usize] += 1;
+                    let left = x == 0 || self.get(x - 1, y);
+                    let right = x == 9 || self.get(x + 1, y);
+                    if left && right {
+                        score += depths[x as usize];
+                    }
                 }
             }
         }
-        if all_empty {
-            break;
-        }
+        score
     }
-    score
 }
@@ -1,4 +1,12 @@
-use crate::{BoardImpl, Implementation, common::*};
+use crate::{
+    common::*,
+    eval::PlacementInfo,
+    notation::{self, ParseBoardError},
+    rules::TopOut,
+    BoardImpl, Features, Implementation,
+};
+use std::fmt;
+use std::str::FromStr;
 
 pub struct NaiveColHeights;
 
@@ -45,29 +53,112 @@ impl BoardImpl for Board {
         }
         rows_cleared as i32
     }
-}
 
-impl Board {
     fn get(&self, x: i8, y: i8) -> bool {
         self.cells[y as usize][x as usize]
     }
 
+    fn column_height(&self, x: i8) -> i8 {
+        self.col_heights[x as usize]
+    }
+
+    fn is_row_full(&self, y: i8) -> bool {
+        self.cells[y as usize] == [true; 10]
+    }
+
+    fn is_row_empty(&self, y: i8) -> bool {
+        self.cells[y as usize] == [false; 10]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.highest() == 0
+    }
+
+    fn corners(&self, x: i8, y: i8) -> u8 {
+        let filled = |x: i8, y: i8| !(0..10).contains(&x) || y < 0 || (y < 40 && self.get(x, y));
+        let mut corners = 0;
+        if filled(x - 1, y + 1) {
+            corners |= CORNER_NW;
+        }
+        if filled(x + 1, y + 1) {
+            corners |= CORNER_NE;
+        }
+        if filled(x - 1, y - 1) {
+            corners |= CORNER_SW;
+        }
+        if filled(x + 1, y - 1) {
+            corners |= CORNER_SE;
+        }
+        corners
+    }
+
+    fn add_garbage_holes(&mut self, rows: u32, holes: u16) -> Result<(), TopOut> {
+        let rows = rows.min(40) as usize;
+        let topped_out = self.highest() as usize + rows > 40;
+        self.cells.copy_within(..40 - rows, rows);
+        let mut garbage = [true; 10];
+        for (x, cell) in garbage.iter_mut().enumerate() {
+            *cell = holes & 1 << x == 0;
+        }
+        for row in &mut self.cells[..rows] {
+            *row = garbage;
+        }
+        for x in 0..10 {
+            let height = self.col_heights[x] as usize;
+            self.col_heights[x] = if height + rows > 40 {
+                (0..40)
+                    .rev()
+                    .find(|&y| self.cells[y][x])
+                    .map_or(0, |y| y as i8 + 1)
+            } else if height > 0 || holes & 1 << x == 0 {
+                (height + rows) as i8
+            } else {
+                0
+            };
+        }
+        if topped_out {
+            Err(TopOut::GarbageOut)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn from_cells(cells: &CellGrid) -> Self {
+        let mut board = Board {
+            cells: *cells,
+            col_heights: [0; 10],
+        };
+        for x in 0..10 {
+            board.col_heights[x] = (0..40)
+                .rev()
+                .find(|&y| board.cells[y][x])
+                .map_or(0, |y| y as i8 + 1);
+        }
+        board
+    }
+
+    fn to_cells(&self) -> CellGrid {
+        self.cells
+    }
+}
+
+impl Board {
     fn highest(&self) -> i8 {
         self.col_heights.iter().copied().max().unwrap()
     }
+}
 
-    #[cfg(test)]
-    pub fn fumenize(self) -> fumen::Fumen {
-        let mut fumen = fumen::Fumen::default();
-        let page = fumen.add_page();
-        for y in 0..23 {
-            for x in 0..10 {
-                if self.get(x as i8, y as i8) {
-                    page.field[y][x] = fumen::CellColor::Grey;
-                }
-            }
-        }
-        fumen
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        notation::write_board(self, f)
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, ParseBoardError> {
+        notation::parse_board(s)
     }
 }
 
@@ -76,9 +167,7 @@ impl Implementation for NaiveColHeights {
 
     const NAME: &'static str = "naive + col heights";
 
-    fn suggest(board: &Board, piece: Piece) -> Option<PieceLocation> {
-        let mut best = None;
-
+    fn for_each_placement(board: &Board, piece: Piece, mut f: impl FnMut(&Board, &PlacementInfo)) {
         for &rotation in piece.sensible_rotations() {
             'placement: for x in 0..10 {
                 let mut piece = PieceLocation {
@@ -89,13 +178,17 @@ impl Implementation for NaiveColHeights {
                 };
 
                 for &(x, y) in &piece.cells() {
-                    if x < 0 || x >= 10 {
+                    if !(0..10).contains(&x) {
                         continue 'placement;
                     }
 
                     piece.y = piece.y.max(board.col_heights[x as usize] - y);
                 }
 
+                if piece.cells().iter().any(|&(_, y)| y >= 40) {
+                    continue;
+                }
+
                 let mut board = *board;
                 board.place(piece);
 
@@ -107,107 +200,87 @@ impl Implementation for NaiveColHeights {
 
                 let lines_cleared = board.collapse_lines();
 
-                let mut low = 40;
-                let mut high = 0;
-                for &(_, y) in &piece.cells() {
-                    low = low.min(y);
-                    high = high.max(y);
-                }
-
-                let landing_height = low as i32 + high as i32;
-                let eroded_piece_cells_metric = lines_cleared * piece_cells_eliminated;
-                let row_transitions = row_transitions(&board);
-                let column_transitions = column_transitions(&board);
-                let buried_holes = buried_holes(&board);
-                let wells = wells(&board);
-
-                let score = 2 * eroded_piece_cells_metric
-                    - landing_height
-                    - 2 * row_transitions
-                    - 2 * column_transitions
-                    - 8 * buried_holes
-                    - 2 * wells;
-
-                match best {
-                    None => best = Some((piece, score)),
-                    Some((_, s)) => {
-                        if score > s {
-                            best = Some((piece, score))
-                        }
-                    }
-                }
+                f(
+                    &board,
+                    &PlacementInfo {
+                        location: piece,
+                        lines_cleared,
+                        piece_cells_eliminated,
+                        perfect_clear: lines_cleared > 0 && board.is_empty(),
+                    },
+                );
             }
         }
-
-        best.map(|(p, _)| p)
     }
 }
 
-fn row_transitions(board: &Board) -> i32 {
-    let highest = board.highest();
-    let extras = (40 - highest as i32) * 2;
-    board
-        .cells
-        .iter()
-        .take(highest as usize)
-        .map(|row| {
-            let mut previous = true;
-            let mut count = 0;
-            for &cell in row {
-                if cell != previous {
+impl Features for Board {
+    fn row_transitions(&self) -> i32 {
+        let highest = self.highest();
+        let extras = (40 - highest as i32) * 2;
+        self.cells
+            .iter()
+            .take(highest as usize)
+            .map(|row| {
+                let mut previous = true;
+                let mut count = 0;
+                for &cell in row {
+                    if cell != previous {
+                        count += 1;
+                    }
+                    previous = cell;
+                }
+                if !previous {
                     count += 1;
                 }
-                previous = cell;
-            }
-            if !previous {
-                count += 1;
-            }
-            count
-        })
-        .sum::<i32>()
-        + extras
-}
+                count
+            })
+            .sum::<i32>()
+            + extras
+    }
 
-fn column_transitions(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut previous = [true; 10];
-    for row in &board.cells[..board.highest() as usize + 1] {
-        count += (0..10).filter(|&x| row[x] != previous[x]).count();
-        previous = *row;
+    fn column_transitions(&self) -> i32 {
+        let mut count = 0;
+        let mut previous = [true; 10];
+        let stack = &self.cells[..self.highest() as usize];
+        for row in stack.iter().chain(std::iter::once(&[false; 10])) {
+            count += (0..10).filter(|&x| row[x] != previous[x]).count();
+            previous = *row;
+        }
+        count as i32
     }
-    count as i32
-}
 
-fn buried_holes(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut is_column_covered = [false; 10];
-    for row in board.cells[..board.highest() as usize].iter().rev() {
-        for x in 0..10 {
-            if is_column_covered[x] && !row[x] {
-                count += 1;
+    fn buried_holes(&self) -> i32 {
+        let mut count = 0;
+        let mut is_column_covered = [false; 10];
+        for row in self.cells[..self.highest() as usize].iter().rev() {
+            for x in 0..10 {
+                if is_column_covered[x] && !row[x] {
+                    count += 1;
+                }
+                is_column_covered[x] |= row[x];
             }
-            is_column_covered[x] |= row[x];
         }
+        count
     }
-    count
-}
 
-fn wells(board: &Board) -> i32 {
-    let mut score = 0;
-    let mut depths = [0; 10];
-    for y in 0..board.highest() {
-        for x in 0..10 {
-            if board.get(x, y) {
-                depths[x as usize] = 0;
-            } else {
-                depths[x as usize] += 1;
-                let left = x == 0 || board.get(x - 1, y);
-                let right = x == 9 || board.get(x + 1, y);
-                if left && right {
-                    score += depths[x as usize];
+    fn wells(&self) -> i32 {
+        let mut score = 0;
+        let mut depths = [0; 10];
+        for y in 0..self.highest() {
+            for x in 0..10 {
+                if self.get(x, y) {
+                    depths[x as usize] = 0;
+                } else {
+                    depths[x as usize] += 1;
+                    let left = x == 0 || self.get(x - 1, y);
+                    let right = x == 9 || self.get(x + 1, y);
+                    if left && right {
+                        score += depths[x as usize];
+                    }
                 }
             }
         }
+        score
     }
-    score
 }
@@ -0,0 +1,157 @@
+//! A plain text board notation. Each row is written as 10 characters, `#` for a filled cell and
+//! `.` for an empty cell, with the top row first. Only rows up to the top of the stack are
+//! written, so an empty board is an empty string.
+//!
+//! ```text
+//! ....#.....
+//! ##..###.##
+//! ###.######
+//! ```
+//!
+//! When parsing, leading and trailing whitespace on each line and blank lines are ignored.
+
+use crate::BoardImpl;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParseBoardError {
+    /// 1-based line number in the input.
+    pub line: usize,
+    /// 1-based character column in the line.
+    pub column: usize,
+    pub kind: ParseBoardErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParseBoardErrorKind {
+    InvalidCell(char),
+    RowTooShort,
+    RowTooLong,
+    TooManyRows,
+}
+
+impl fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match self.kind {
+            ParseBoardErrorKind::InvalidCell(c) => write!(f, "invalid cell {:?}", c),
+            ParseBoardErrorKind::RowTooShort => write!(f, "row has fewer than 10 cells"),
+            ParseBoardErrorKind::RowTooLong => write!(f, "row has more than 10 cells"),
+            ParseBoardErrorKind::TooManyRows => write!(f, "board has more than 40 rows"),
+        }
+    }
+}
+
+impl std::error::Error for ParseBoardError {}
+
+pub(crate) fn write_board<B: BoardImpl>(board: &B, f: &mut fmt::Formatter) -> fmt::Result {
+    let height = (0..10).map(|x| board.column_height(x)).max().unwrap();
+    for y in (0..height).rev() {
+        for x in 0..10 {
+            f.write_str(if board.get(x, y) { "#" } else { "." })?;
+        }
+        if y != 0 {
+            f.write_str("\n")?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_board<B: BoardImpl>(s: &str) -> Result<B, ParseBoardError> {
+    let mut rows = vec![];
+    for (i, line) in s.lines().enumerate() {
+        let row = line.trim();
+        if row.is_empty() {
+            continue;
+        }
+        let indent = line.chars().count() - line.trim_start().chars().count();
+        let error = |column, kind| ParseBoardError {
+            line: i + 1,
+            column: indent + column + 1,
+            kind,
+        };
+
+        if rows.len() == 40 {
+            return Err(error(0, ParseBoardErrorKind::TooManyRows));
+        }
+        let mut cells = [false; 10];
+        let mut width = 0;
+        for (x, c) in row.chars().enumerate() {
+            if x == 10 {
+                return Err(error(x, ParseBoardErrorKind::RowTooLong));
+            }
+            cells[x] = match c {
+                '#' => true,
+                '.' => false,
+                _ => return Err(error(x, ParseBoardErrorKind::InvalidCell(c))),
+            };
+            width += 1;
+        }
+        if width < 10 {
+            return Err(error(width, ParseBoardErrorKind::RowTooShort));
+        }
+        rows.push(cells);
+    }
+
+    let mut cells = [[false; 10]; 40];
+    for (y, row) in rows.into_iter().rev().enumerate() {
+        cells[y] = row;
+    }
+    Ok(B::from_cells(&cells))
+}
+
+#[cfg(test)]
+#[test]
+fn round_trip() {
+    use crate::{naive, Implementation};
+    use std::str::FromStr;
+
+    fn check<I: Implementation>(text: &str, expected: &naive::Board)
+    where
+        I::Board: FromStr<Err = ParseBoardError> + fmt::Display,
+    {
+        let board: I::Board = text.parse().unwrap();
+        assert_eq!(board.to_cells(), expected.to_cells());
+        assert_eq!(board.to_string(), text);
+    }
+
+    let text = "....#.....\n##..###.##\n###.######";
+    let board: naive::Board = text.parse().unwrap();
+    assert!(board.get(4, 2));
+    assert!(!board.get(3, 0));
+    assert_eq!(board.to_string(), text);
+    for_each_implementation!(|I| check::<I>(text, &board));
+
+    let indented = "
+        ....#.....
+        ##..###.##
+        ###.######
+    ";
+    assert_eq!(indented.parse::<naive::Board>(), Ok(board));
+    assert_eq!("".parse::<naive::Board>(), Ok(naive::Board::new()));
+    assert_eq!(naive::Board::new().to_string(), "");
+}
+
+#[cfg(test)]
+#[test]
+fn errors() {
+    use crate::naive;
+
+    let error = |line, column, kind| Err(ParseBoardError { line, column, kind });
+    assert_eq!(
+        "..........\n  ....x.....".parse::<naive::Board>(),
+        error(2, 7, ParseBoardErrorKind::InvalidCell('x'))
+    );
+    assert_eq!(
+        "#########".parse::<naive::Board>(),
+        error(1, 10, ParseBoardErrorKind::RowTooShort)
+    );
+    assert_eq!(
+        "\n###########".parse::<naive::Board>(),
+        error(2, 11, ParseBoardErrorKind::RowTooLong)
+    );
+    assert_eq!(
+        "..........\n".repeat(41).parse::<naive::Board>(),
+        error(41, 1, ParseBoardErrorKind::TooManyRows)
+    );
+}
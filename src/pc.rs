@@ -0,0 +1,144 @@
+//! Perfect clear search.
+//!
+//! The placements are the ones produced by `Implementation::for_each_placement`, so the search
+//! speed depends on how fast each representation generates and applies them.
+
+use crate::common::*;
+use crate::{BoardImpl, Implementation};
+
+/// Every sequence of placements that empties the board without any piece going above the
+/// bottom `max_lines` rows (which move down as lines are cleared). Pieces are taken from `queue`
+/// in order; if `use_hold` is set, the current piece can be swapped with the one in `hold`, or
+/// with the next piece if the hold slot is empty. Not every piece has to be used.
+pub fn solve<I: Implementation>(
+    board: &I::Board,
+    queue: &[Piece],
+    hold: Option<Piece>,
+    use_hold: bool,
+    max_lines: i32,
+) -> Vec<Vec<PieceLocation>> {
+    let mut search = Search {
+        use_hold,
+        path: vec![],
+        solutions: vec![],
+    };
+    let mut filled = 0;
+    for y in 0..40 {
+        for x in 0..10 {
+            filled += board.get(x, y) as i32;
+        }
+    }
+    if (0..10).all(|x| board.column_height(x) as i32 <= max_lines) {
+        search.search::<I>(board, queue, hold, max_lines, filled);
+    }
+    search.solutions.sort();
+    search.solutions.dedup();
+    search.solutions
+}
+
+struct Search {
+    use_hold: bool,
+    path: Vec<PieceLocation>,
+    solutions: Vec<Vec<PieceLocation>>,
+}
+
+impl Search {
+    /// `limit` is the number of rows pieces can still be placed in, and `filled` is the number of
+    /// filled cells, which are all below it.
+    fn search<I: Implementation>(
+        &mut self,
+        board: &I::Board,
+        queue: &[Piece],
+        hold: Option<Piece>,
+        limit: i32,
+        filled: i32,
+    ) {
+        // the empty cells below the limit have to be filled by whole pieces
+        let empty = limit * 10 - filled;
+        let pieces_left = queue.len() as i32 + hold.is_some() as i32;
+        if empty % 4 != 0 || empty > pieces_left * 4 {
+            return;
+        }
+
+        let (&current, rest) = match queue.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        self.place::<I>(board, current, rest, hold, limit, filled);
+        if self.use_hold {
+            match (hold, rest.split_first()) {
+                (Some(held), _) if held != current => {
+                    self.place::<I>(board, held, rest, Some(current), limit, filled)
+                }
+                (None, Some((&next, rest))) => {
+                    self.place::<I>(board, next, rest, Some(current), limit, filled)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn place<I: Implementation>(
+        &mut self,
+        board: &I::Board,
+        piece: Piece,
+        queue: &[Piece],
+        hold: Option<Piece>,
+        limit: i32,
+        filled: i32,
+    ) {
+        I::for_each_placement(board, piece, |board, placement| {
+            if placement
+                .location
+                .cells()
+                .iter()
+                .any(|&(_, y)| y as i32 >= limit)
+            {
+                return;
+            }
+            self.path.push(placement.location);
+            if placement.perfect_clear {
+                self.solutions.push(self.path.clone());
+            } else {
+                let lines = placement.lines_cleared;
+                self.search::<I>(board, queue, hold, limit - lines, filled + 4 - lines * 10);
+            }
+            self.path.pop();
+        });
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn three_lines() {
+    use crate::{naive, Naive};
+
+    let board: naive::Board = "
+        ###....###
+        ###....###
+        ###....###
+    "
+    .parse()
+    .unwrap();
+    let i = |y| PieceLocation {
+        piece: Piece::I,
+        rotation: Rotation::North,
+        x: 4,
+        y,
+    };
+    // each I clears a line, so they all go in the bottom row
+    let queue = [Piece::I, Piece::I, Piece::I];
+    assert_eq!(
+        solve::<Naive>(&board, &queue, None, false, 3),
+        vec![vec![i(0), i(0), i(0)]]
+    );
+    assert!(solve::<Naive>(&board, &queue, None, false, 2).is_empty());
+
+    // the O can only be placed by holding it
+    let queue = [Piece::O, Piece::I, Piece::I, Piece::I];
+    assert!(solve::<Naive>(&board, &queue, None, false, 3).is_empty());
+    assert_eq!(
+        solve::<Naive>(&board, &queue, None, true, 3),
+        vec![vec![i(0), i(0), i(0)]]
+    );
+}
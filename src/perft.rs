@@ -0,0 +1,308 @@
+//! Counting placement sequences, like perft in chess engines.
+//!
+//! Placements are the ones produced by `Implementation::for_each_placement`, so the count
+//! measures how fast each representation generates and applies placements.
+
+use crate::common::*;
+use crate::movegen::DropMode;
+use crate::rotation::RotationSystem;
+use crate::rules::Rules;
+use crate::{BoardImpl, Implementation};
+
+/// The number of sequences of placements of every piece in `queue`, in order. The depth is the
+/// length of the queue.
+pub fn perft<I: Implementation>(board: &I::Board, queue: &[Piece]) -> u64 {
+    match queue {
+        [] => 1,
+        &[piece] => {
+            let mut count = 0;
+            I::for_each_placement(board, piece, |_, _| count += 1);
+            count
+        }
+        &[piece, ref rest @ ..] => {
+            let mut count = 0;
+            I::for_each_placement(board, piece, |board, _| count += perft::<I>(board, rest));
+            count
+        }
+    }
+}
+
+/// Like `perft`, but with the placements found by `movegen` under the rules and rotation system,
+/// so tucks, spins and 180 degree rotations are counted too. Sequences end early if a piece
+/// blocks out or locks out.
+pub fn perft_movegen<R: RotationSystem, B: BoardImpl + Copy>(
+    rules: &Rules,
+    system: &R,
+    board: &B,
+    queue: &[Piece],
+    mode: DropMode,
+) -> u64 {
+    let (&piece, rest) = match queue.split_first() {
+        Some(split) => split,
+        None => return 1,
+    };
+    let placements = match rules.find_placements(system, board, piece, mode) {
+        Ok(placements) => placements,
+        Err(_) => return 0,
+    };
+    if rest.is_empty() {
+        return placements.len() as u64;
+    }
+    placements
+        .iter()
+        .map(|placement| {
+            let mut board = *board;
+            match rules.lock(&mut board, placement.location) {
+                Ok(_) => perft_movegen(rules, system, &board, rest, mode),
+                Err(_) => 0,
+            }
+        })
+        .sum()
+}
+
+/// The perft count below each placement of the first piece of `queue`, sorted by location.
+pub fn divide<I: Implementation>(board: &I::Board, queue: &[Piece]) -> Vec<(PieceLocation, u64)> {
+    let mut counts = vec![];
+    if let Some((&piece, rest)) = queue.split_first() {
+        I::for_each_placement(board, piece, |board, placement| {
+            counts.push((placement.location, perft::<I>(board, rest)))
+        });
+    }
+    counts.sort();
+    counts
+}
+
+/// Where two implementations first disagree on a perft count.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The placements leading to the board where the disagreement is.
+    pub path: Vec<PieceLocation>,
+    /// The first placement on that board whose count differs.
+    pub location: PieceLocation,
+    /// The count below `location` for each implementation, or `None` if it doesn't generate it.
+    pub counts: [Option<u64>; 2],
+}
+
+/// Compares the divide output of two implementations, recursing into the first branch with
+/// different counts. Returns `None` if they agree.
+pub fn find_divergence<A: Implementation, B: Implementation>(
+    a: &A::Board,
+    b: &B::Board,
+    queue: &[Piece],
+) -> Option<Divergence> {
+    let (location, counts) = first_mismatch(&divide::<A>(a, queue), &divide::<B>(b, queue))?;
+    if let [Some(_), Some(_)] = counts {
+        let rest = &queue[1..];
+        let deeper = with_board_after::<A, _>(a, location, |a| {
+            with_board_after::<B, _>(b, location, |b| find_divergence::<A, B>(a, b, rest))
+        });
+        if let Some(Some(Some(mut divergence))) = deeper {
+            divergence.path.insert(0, location);
+            return Some(divergence);
+        }
+    }
+    Some(Divergence {
+        path: vec![],
+        location,
+        counts,
+    })
+}
+
+fn first_mismatch(
+    a: &[(PieceLocation, u64)],
+    b: &[(PieceLocation, u64)],
+) -> Option<(PieceLocation, [Option<u64>; 2])> {
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return None,
+            (Some(&&(location, count)), None) => return Some((location, [Some(count), None])),
+            (None, Some(&&(location, count))) => return Some((location, [None, Some(count)])),
+            (Some(&&(la, ca)), Some(&&(lb, cb))) => {
+                if la < lb {
+                    return Some((la, [Some(ca), None]));
+                } else if lb < la {
+                    return Some((lb, [None, Some(cb)]));
+                } else if ca != cb {
+                    return Some((la, [Some(ca), Some(cb)]));
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Calls `f` with the board after the placement, if the implementation generates it.
+fn with_board_after<I: Implementation, R>(
+    board: &I::Board,
+    location: PieceLocation,
+    f: impl FnOnce(&I::Board) -> R,
+) -> Option<R> {
+    let mut f = Some(f);
+    let mut result = None;
+    I::for_each_placement(board, location.piece, |board, placement| {
+        if placement.location == location {
+            result = f.take().map(|f| f(board));
+        }
+    });
+    result
+}
+
+#[cfg(test)]
+#[test]
+fn empty_board() {
+    use crate::{BoardImpl, Naive};
+
+    let board = crate::naive::Board::new();
+    assert_eq!(perft::<Naive>(&board, &[]), 1);
+    assert_eq!(perft::<Naive>(&board, &[Piece::I]), 17);
+    assert_eq!(perft::<Naive>(&board, &[Piece::O]), 9);
+    assert_eq!(perft::<Naive>(&board, &[Piece::T]), 34);
+    assert_eq!(perft::<Naive>(&board, &[Piece::O, Piece::O]), 81);
+}
+
+#[cfg(test)]
+#[test]
+fn divergence() {
+    use crate::eval::PlacementInfo;
+    use crate::{BoardImpl, Naive};
+
+    // Loses vertical I placements against the left wall once the board isn't empty.
+    struct Broken;
+    impl Implementation for Broken {
+        type Board = crate::naive::Board;
+        const NAME: &'static str = "broken";
+
+        fn for_each_placement(
+            board: &Self::Board,
+            piece: Piece,
+            mut f: impl FnMut(&Self::Board, &PlacementInfo),
+        ) {
+            let empty = board.is_empty();
+            Naive::for_each_placement(board, piece, |board, placement| {
+                let location = placement.location;
+                if empty
+                    || location.piece != Piece::I
+                    || location.rotation != Rotation::East
+                    || location.x != 0
+                {
+                    f(board, placement);
+                }
+            });
+        }
+    }
+
+    let board = crate::naive::Board::new();
+    let queue = [Piece::O, Piece::I];
+    assert_eq!(
+        find_divergence::<Naive, Naive>(&board, &board, &queue),
+        None
+    );
+    assert_eq!(
+        find_divergence::<Naive, Broken>(&board, &board, &queue),
+        Some(Divergence {
+            path: vec![PieceLocation {
+                piece: Piece::O,
+                rotation: Rotation::North,
+                x: 0,
+                y: 0,
+            }],
+            location: PieceLocation {
+                piece: Piece::I,
+                rotation: Rotation::East,
+                x: 0,
+                y: 4,
+            },
+            counts: [Some(1), None],
+        })
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn flip_only_placement() {
+    use crate::naive;
+    use crate::rotation::{RotationSystem, Srs};
+
+    // The T pointing down at (1, 1) is under the overhang in row 2 and can't slide in over
+    // (2, 0), and every quarter turn into it overlaps the stack. Flipping the T resting at (2, 1)
+    // gets there with the last SRS+ kick, one column left, after the first five are blocked by
+    // (2, 0), (1, 2), (4, 2), (0, 2) and (4, 1). That is the only placement 180 degree rotations
+    // add.
+    let board: naive::Board = "
+        ##..#.....
+        ....#.....
+        #.#.......
+    "
+    .parse()
+    .unwrap();
+    let north = PieceLocation {
+        piece: Piece::T,
+        rotation: Rotation::North,
+        x: 2,
+        y: 1,
+    };
+    let south = PieceLocation {
+        rotation: Rotation::South,
+        x: 1,
+        ..north
+    };
+    assert_eq!(Srs.rotate(&board, north, Rotation::South), Some((south, 5)));
+
+    let rules = Rules::default();
+    let flip = Rules {
+        allow_180: true,
+        ..rules
+    };
+    for &mode in &[DropMode::HardDrop, DropMode::SoftDrop] {
+        let locations = |rules: &Rules| -> Vec<_> {
+            let placements = rules.find_placements(&Srs, &board, Piece::T, mode);
+            placements.unwrap().iter().map(|p| p.location).collect()
+        };
+        let without = locations(&rules);
+        let with = locations(&flip);
+        assert!(without.contains(&north));
+        assert!(!without.contains(&south));
+        assert!(with.contains(&south));
+        assert_eq!(
+            perft_movegen(&flip, &Srs, &board, &[Piece::T], mode),
+            perft_movegen(&rules, &Srs, &board, &[Piece::T], mode) + 1
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn movegen_reference_counts() {
+    // These were recorded from this implementation, so they only catch changes to move
+    // generation. `flip_only_placement` checks a case by hand.
+    use crate::naive;
+    use crate::rotation::{Ars, Nes, Srs};
+
+    let board: naive::Board = "
+        ####......
+        ##.....###
+        ##.#######
+        ##..######
+        ##.#######
+    "
+    .parse()
+    .unwrap();
+    let queue = [Piece::T, Piece::L, Piece::I];
+    let rules = Rules::default();
+    let flip = Rules {
+        allow_180: true,
+        ..rules
+    };
+    // the 180 degree kicks let the T and L reach a few more places
+    let counts = [
+        perft_movegen(&rules, &Srs, &board, &queue, DropMode::HardDrop),
+        perft_movegen(&rules, &Srs, &board, &queue, DropMode::SoftDrop),
+        perft_movegen(&flip, &Srs, &board, &queue, DropMode::HardDrop),
+        perft_movegen(&flip, &Srs, &board, &queue, DropMode::SoftDrop),
+        perft_movegen(&rules, &Ars, &board, &queue, DropMode::SoftDrop),
+        perft_movegen(&rules, &Nes, &board, &queue, DropMode::SoftDrop),
+    ];
+    assert_eq!(counts, [22089, 23914, 22543, 24356, 22626, 22464]);
+}
@@ -0,0 +1,200 @@
+//! Piece randomizers. Every generator is seeded, so the same seed always deals the same pieces.
+
+use crate::common::Piece;
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+
+pub trait PieceGenerator {
+    fn next_piece(&mut self) -> Piece;
+
+    /// The next `n` pieces.
+    fn pieces(&mut self, n: usize) -> Vec<Piece> {
+        (0..n).map(|_| self.next_piece()).collect()
+    }
+}
+
+/// Deals each piece with equal probability, independently of the previous ones.
+pub struct Uniform {
+    rng: Pcg64,
+}
+
+impl Uniform {
+    pub fn new(seed: u64) -> Self {
+        Uniform {
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+}
+
+impl PieceGenerator for Uniform {
+    fn next_piece(&mut self) -> Piece {
+        *Piece::ALL.choose(&mut self.rng).unwrap()
+    }
+}
+
+/// Deals shuffled bags of every piece, as in guideline games.
+pub struct SevenBag {
+    rng: Pcg64,
+    bag: Vec<Piece>,
+}
+
+impl SevenBag {
+    pub fn new(seed: u64) -> Self {
+        SevenBag {
+            rng: Pcg64::seed_from_u64(seed),
+            bag: Vec::with_capacity(7),
+        }
+    }
+}
+
+impl PieceGenerator for SevenBag {
+    fn next_piece(&mut self) -> Piece {
+        if self.bag.is_empty() {
+            self.bag.extend_from_slice(&Piece::ALL);
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// Deals shuffled bags of two of every piece.
+pub struct FourteenBag {
+    rng: Pcg64,
+    bag: Vec<Piece>,
+}
+
+impl FourteenBag {
+    pub fn new(seed: u64) -> Self {
+        FourteenBag {
+            rng: Pcg64::seed_from_u64(seed),
+            bag: Vec::with_capacity(14),
+        }
+    }
+}
+
+impl PieceGenerator for FourteenBag {
+    fn next_piece(&mut self) -> Piece {
+        if self.bag.is_empty() {
+            self.bag.extend_from_slice(&Piece::ALL);
+            self.bag.extend_from_slice(&Piece::ALL);
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// The TGM randomizer: rolls up to 6 times for a piece that isn't one of the last 4 dealt, and
+/// keeps the last roll if they all are. The history starts as Z, Z, S, S and the first piece is
+/// never S, Z or O.
+pub struct Tgm {
+    rng: Pcg64,
+    history: [Piece; 4],
+    first: bool,
+}
+
+impl Tgm {
+    pub fn new(seed: u64) -> Self {
+        Tgm {
+            rng: Pcg64::seed_from_u64(seed),
+            history: [Piece::Z, Piece::Z, Piece::S, Piece::S],
+            first: true,
+        }
+    }
+}
+
+impl PieceGenerator for Tgm {
+    fn next_piece(&mut self) -> Piece {
+        let piece = if self.first {
+            self.first = false;
+            *[Piece::I, Piece::T, Piece::L, Piece::J]
+                .choose(&mut self.rng)
+                .unwrap()
+        } else {
+            let mut piece = Piece::I;
+            for _ in 0..6 {
+                piece = *Piece::ALL.choose(&mut self.rng).unwrap();
+                if !self.history.contains(&piece) {
+                    break;
+                }
+            }
+            piece
+        };
+        self.history.rotate_left(1);
+        self.history[3] = piece;
+        piece
+    }
+}
+
+/// The NES randomizer: rolls one of 8 outcomes, and rolls again from the 7 pieces if it got the
+/// extra outcome or repeated the previous piece.
+pub struct Nes {
+    rng: Pcg64,
+    previous: Option<Piece>,
+}
+
+impl Nes {
+    pub fn new(seed: u64) -> Self {
+        Nes {
+            rng: Pcg64::seed_from_u64(seed),
+            previous: None,
+        }
+    }
+}
+
+impl PieceGenerator for Nes {
+    fn next_piece(&mut self) -> Piece {
+        let piece = match Piece::ALL.get(self.rng.gen_range(0..8)) {
+            Some(&piece) if Some(piece) != self.previous => piece,
+            _ => *Piece::ALL.choose(&mut self.rng).unwrap(),
+        };
+        self.previous = Some(piece);
+        piece
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn bags() {
+    let mut seven = SevenBag::new(1);
+    for _ in 0..10 {
+        let mut bag = seven.pieces(7);
+        bag.sort();
+        assert_eq!(bag, Piece::ALL);
+    }
+
+    let mut fourteen = FourteenBag::new(1);
+    for _ in 0..10 {
+        let bag = fourteen.pieces(14);
+        for piece in &Piece::ALL {
+            assert_eq!(bag.iter().filter(|&p| p == piece).count(), 2);
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn reproducible() {
+    fn check<G: PieceGenerator>(new: impl Fn(u64) -> G) {
+        let pieces = new(7).pieces(1000);
+        assert_eq!(new(7).pieces(1000), pieces);
+        assert_ne!(new(8).pieces(1000), pieces);
+        for piece in &Piece::ALL {
+            assert!(pieces.contains(piece));
+        }
+    }
+
+    check(Uniform::new);
+    check(SevenBag::new);
+    check(FourteenBag::new);
+    check(Tgm::new);
+    check(Nes::new);
+}
+
+#[cfg(test)]
+#[test]
+fn tgm_first_piece() {
+    for seed in 0..100 {
+        let first = Tgm::new(seed).next_piece();
+        assert!(![Piece::S, Piece::Z, Piece::O].contains(&first));
+    }
+}
@@ -0,0 +1,376 @@
+//! Rotation systems: the piece shapes for each rotation state and how rotations kick.
+//!
+//! Locations passed to a rotation system use its own shapes. `normalize` converts them to the
+//! crate's `PieceLocation` shapes, which are the SRS ones, so they can be placed on a board.
+
+use crate::common::*;
+use crate::BoardImpl;
+
+pub trait RotationSystem {
+    /// The cells of the piece relative to its position.
+    fn cells(&self, piece: Piece, rotation: Rotation) -> [(i8, i8); 4];
+
+    /// Rotates the piece, returning the new location and the index of the kick that was used,
+    /// or `None` if the rotation fails.
+    fn rotate<B: BoardImpl>(
+        &self,
+        board: &B,
+        location: PieceLocation,
+        to: Rotation,
+    ) -> Option<(PieceLocation, usize)>;
+
+    /// Whether a T rotation between these states using this kick is always a full T-spin.
+    fn upgrades_tspin(&self, _from: Rotation, _to: Rotation, _kick: usize) -> bool {
+        false
+    }
+
+    fn location_cells(&self, location: PieceLocation) -> [(i8, i8); 4] {
+        let mut cells = self.cells(location.piece, location.rotation);
+        for cell in &mut cells {
+            cell.0 += location.x;
+            cell.1 += location.y;
+        }
+        cells
+    }
+
+    /// Whether the piece overlaps a filled cell, a wall or the floor.
+    fn collides<B: BoardImpl>(&self, board: &B, location: PieceLocation) -> bool {
+        self.location_cells(location)
+            .iter()
+            .any(|&(x, y)| !(0..10).contains(&x) || y < 0 || (y < 40 && board.get(x, y)))
+    }
+
+    /// The canonical `PieceLocation` with the same cells.
+    fn normalize(&self, location: PieceLocation) -> PieceLocation {
+        let mut cells = self.location_cells(location);
+        cells.sort();
+        for &rotation in location.piece.sensible_rotations() {
+            let mut shape = location.piece.cells();
+            for cell in &mut shape {
+                *cell = rotation.rotate_cell(*cell);
+            }
+            shape.sort();
+            let (dx, dy) = (cells[0].0 - shape[0].0, cells[0].1 - shape[0].1);
+            if (0..4).all(|i| cells[i] == (shape[i].0 + dx, shape[i].1 + dy)) {
+                return PieceLocation {
+                    piece: location.piece,
+                    rotation,
+                    x: dx,
+                    y: dy,
+                };
+            }
+        }
+        unreachable!("{:?} has a shape that isn't one of the piece's", location)
+    }
+}
+
+/// The Super Rotation System used by guideline games.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn cells(&self, piece: Piece, rotation: Rotation) -> [(i8, i8); 4] {
+        PieceLocation {
+            piece,
+            rotation,
+            x: 0,
+            y: 0,
+        }
+        .cells()
+    }
+
+    fn rotate<B: BoardImpl>(
+        &self,
+        board: &B,
+        location: PieceLocation,
+        to: Rotation,
+    ) -> Option<(PieceLocation, usize)> {
+        Srs::kicks(location.piece, location.rotation, to)
+            .map(|(dx, dy)| PieceLocation {
+                rotation: to,
+                x: location.x + dx,
+                y: location.y + dy,
+                ..location
+            })
+            .enumerate()
+            .find(|&(_, rotated)| !self.collides(board, rotated))
+            .map(|(kick, rotated)| (rotated, kick))
+    }
+
+    /// The TST kick (the fifth test of a quarter turn) always gives a full T-spin.
+    fn upgrades_tspin(&self, from: Rotation, to: Rotation, kick: usize) -> bool {
+        to != from.flip() && kick == 4
+    }
+
+    fn normalize(&self, location: PieceLocation) -> PieceLocation {
+        location.canonical()
+    }
+}
+
+impl Srs {
+    /// The SRS kick tests for rotating between two states, in the order they are tried. 180
+    /// degree rotations use the SRS+ kicks from TETR.IO.
+    pub fn kicks(piece: Piece, from: Rotation, to: Rotation) -> impl Iterator<Item = (i8, i8)> {
+        let (quarter, flip) = if to == from.flip() {
+            (&[][..], &FLIP_KICKS[index(from)][..])
+        } else {
+            (offsets(piece, from), &[][..])
+        };
+        // SRS+ kicks are relative to the piece's bounding box, which is where the first offset
+        // puts it
+        let (fx, fy) = offsets(piece, from)[0];
+        let (tx, ty) = offsets(piece, to)[0];
+        quarter
+            .iter()
+            .zip(offsets(piece, to))
+            .map(|(&(fx, fy), &(tx, ty))| (fx - tx, fy - ty))
+            .chain(
+                flip.iter()
+                    .map(move |&(dx, dy)| (fx - tx + dx, fy - ty + dy)),
+            )
+    }
+}
+
+// The SRS offset tables. The kicks for a rotation are the offsets of the initial state minus the
+// offsets of the final state.
+const JLSTZ_OFFSETS: [[(i8, i8); 5]; 4] = [
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const I_OFFSETS: [[(i8, i8); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
+    [(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
+    [(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
+    [(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
+];
+const O_OFFSETS: [[(i8, i8); 1]; 4] = [[(0, 0)], [(0, -1)], [(-1, -1)], [(-1, 0)]];
+
+// The SRS+ 180 degree kicks, for every piece, indexed by the initial state.
+const FLIP_KICKS: [[(i8, i8); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+fn index(rotation: Rotation) -> usize {
+    match rotation {
+        Rotation::North => 0,
+        Rotation::East => 1,
+        Rotation::South => 2,
+        Rotation::West => 3,
+    }
+}
+
+fn offsets(piece: Piece, rotation: Rotation) -> &'static [(i8, i8)] {
+    match piece {
+        Piece::I => &I_OFFSETS[index(rotation)],
+        Piece::O => &O_OFFSETS[index(rotation)],
+        _ => &JLSTZ_OFFSETS[index(rotation)],
+    }
+}
+
+/// The Arika Rotation System used by the TGM games. Pieces spawn flat side up and rotate inside a
+/// 3x3 box, kicking one column right and then left. The I piece never kicks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn cells(&self, piece: Piece, rotation: Rotation) -> [(i8, i8); 4] {
+        use Rotation::*;
+        match (piece, rotation) {
+            (Piece::I, North) | (Piece::I, South) => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            (Piece::I, _) => [(1, 1), (1, 0), (1, -1), (1, -2)],
+            (Piece::O, _) => [(0, 0), (1, 0), (0, -1), (1, -1)],
+            (Piece::T, North) => [(-1, 0), (0, 0), (1, 0), (0, -1)],
+            (Piece::T, East) => [(0, 1), (-1, 0), (0, 0), (0, -1)],
+            (Piece::T, South) => [(0, 0), (-1, -1), (0, -1), (1, -1)],
+            (Piece::T, West) => [(0, 1), (0, 0), (1, 0), (0, -1)],
+            (Piece::L, North) => [(-1, 0), (0, 0), (1, 0), (-1, -1)],
+            (Piece::L, East) => [(-1, 1), (0, 1), (0, 0), (0, -1)],
+            (Piece::L, South) => [(1, 0), (-1, -1), (0, -1), (1, -1)],
+            (Piece::L, West) => [(0, 1), (0, 0), (0, -1), (1, -1)],
+            (Piece::J, North) => [(-1, 0), (0, 0), (1, 0), (1, -1)],
+            (Piece::J, East) => [(0, 1), (0, 0), (-1, -1), (0, -1)],
+            (Piece::J, South) => [(-1, 0), (-1, -1), (0, -1), (1, -1)],
+            (Piece::J, West) => [(0, 1), (1, 1), (0, 0), (0, -1)],
+            (Piece::S, North) | (Piece::S, South) => [(0, 0), (1, 0), (-1, -1), (0, -1)],
+            (Piece::S, _) => [(-1, 1), (-1, 0), (0, 0), (0, -1)],
+            (Piece::Z, North) | (Piece::Z, South) => [(-1, 0), (0, 0), (0, -1), (1, -1)],
+            (Piece::Z, _) => [(1, 1), (0, 0), (1, 0), (0, -1)],
+        }
+    }
+
+    fn rotate<B: BoardImpl>(
+        &self,
+        board: &B,
+        location: PieceLocation,
+        to: Rotation,
+    ) -> Option<(PieceLocation, usize)> {
+        let rotated = PieceLocation {
+            rotation: to,
+            ..location
+        };
+        if !self.collides(board, rotated) {
+            return Some((rotated, 0));
+        }
+        if location.piece == Piece::I {
+            return None;
+        }
+
+        // The center column rule: L, J and T don't kick if the first blocked cell of the new
+        // shape, in reading order, is in the center column.
+        if let Piece::L | Piece::J | Piece::T = location.piece {
+            let blocked = |(x, y): (i8, i8)| {
+                let (x, y) = (x + location.x, y + location.y);
+                !(0..10).contains(&x) || y < 0 || (y < 40 && board.get(x, y))
+            };
+            let mut cells = self.cells(location.piece, to);
+            cells.sort_by_key(|&(x, y)| (-y, x));
+            if let Some(&(0, _)) = cells.iter().find(|&&cell| blocked(cell)) {
+                return None;
+            }
+        }
+
+        [1, -1]
+            .iter()
+            .map(|&dx| PieceLocation {
+                x: rotated.x + dx,
+                ..rotated
+            })
+            .enumerate()
+            .find(|&(_, kicked)| !self.collides(board, kicked))
+            .map(|(i, kicked)| (kicked, i + 1))
+    }
+}
+
+/// The Nintendo Rotation System used by NES Tetris. T, L and J rotate about their center, I, S
+/// and Z only have two states, and nothing kicks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Nes;
+
+impl RotationSystem for Nes {
+    fn cells(&self, piece: Piece, rotation: Rotation) -> [(i8, i8); 4] {
+        use Rotation::*;
+        match (piece, rotation) {
+            (Piece::I, North) | (Piece::I, South) => [(-2, 0), (-1, 0), (0, 0), (1, 0)],
+            (Piece::I, _) => [(0, 2), (0, 1), (0, 0), (0, -1)],
+            (Piece::O, _) => [(-1, 0), (0, 0), (-1, -1), (0, -1)],
+            (Piece::S, North) | (Piece::S, South) => [(0, 0), (1, 0), (-1, -1), (0, -1)],
+            (Piece::S, _) => [(0, 1), (0, 0), (1, 0), (1, -1)],
+            (Piece::Z, North) | (Piece::Z, South) => [(-1, 0), (0, 0), (0, -1), (1, -1)],
+            (Piece::Z, _) => [(1, 1), (0, 0), (1, 0), (0, -1)],
+            // the SRS shapes turned upside down, so they spawn flat side up
+            (piece, rotation) => Srs.cells(piece, rotation.cw().cw()),
+        }
+    }
+
+    fn rotate<B: BoardImpl>(
+        &self,
+        board: &B,
+        location: PieceLocation,
+        to: Rotation,
+    ) -> Option<(PieceLocation, usize)> {
+        let rotated = PieceLocation {
+            rotation: to,
+            ..location
+        };
+        if self.collides(board, rotated) {
+            None
+        } else {
+            Some((rotated, 0))
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn shapes_normalize() {
+    fn check<R: RotationSystem>(system: &R) {
+        for &piece in &Piece::ALL {
+            for &rotation in &Rotation::ALL {
+                let location = PieceLocation {
+                    piece,
+                    rotation,
+                    x: 4,
+                    y: 10,
+                };
+                let mut cells = system.location_cells(location);
+                let mut normalized = system.normalize(location).cells();
+                cells.sort();
+                normalized.sort();
+                assert_eq!(cells, normalized);
+            }
+        }
+    }
+
+    check(&Srs);
+    check(&Ars);
+    check(&Nes);
+}
+
+#[cfg(test)]
+#[test]
+fn flip_in_bounding_box() {
+    use crate::naive;
+
+    // A flipped I moves down a row inside its 4x4 bounding box, like in TETR.IO.
+    let board = naive::Board::new();
+    let location = PieceLocation {
+        piece: Piece::I,
+        rotation: Rotation::North,
+        x: 4,
+        y: 20,
+    };
+    let (flipped, kick) = Srs.rotate(&board, location, Rotation::South).unwrap();
+    assert_eq!(kick, 0);
+    let mut cells = flipped.cells();
+    cells.sort();
+    assert_eq!(cells, [(3, 19), (4, 19), (5, 19), (6, 19)]);
+}
+
+#[cfg(test)]
+#[test]
+fn ars_center_column() {
+    use crate::naive;
+
+    let upside_down = PieceLocation {
+        piece: Piece::T,
+        rotation: Rotation::South,
+        x: 4,
+        y: 5,
+    };
+
+    // blocked to the left of the center, so the T kicks right
+    let mut board = naive::Board::new();
+    board.place(PieceLocation {
+        piece: Piece::O,
+        rotation: Rotation::North,
+        x: 2,
+        y: 5,
+    });
+    assert_eq!(
+        Ars.rotate(&board, upside_down, Rotation::East),
+        Some((
+            PieceLocation {
+                rotation: Rotation::East,
+                x: 5,
+                ..upside_down
+            },
+            1
+        ))
+    );
+    assert_eq!(Nes.rotate(&board, upside_down, Rotation::East), None);
+
+    // blocked in the center column, so it can't kick
+    let mut board = naive::Board::new();
+    board.place(PieceLocation {
+        piece: Piece::I,
+        rotation: Rotation::East,
+        x: 4,
+        y: 8,
+    });
+    assert_eq!(Ars.rotate(&board, upside_down, Rotation::East), None);
+}
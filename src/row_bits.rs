@@ -1,4 +1,12 @@
-use crate::{BoardImpl, Implementation, common::*};
+use crate::{
+    common::*,
+    eval::PlacementInfo,
+    notation::{self, ParseBoardError},
+    rules::TopOut,
+    BoardImpl, Features, Implementation,
+};
+use std::fmt;
+use std::str::FromStr;
 
 pub struct RowBits;
 
@@ -26,9 +34,6 @@ impl BoardImpl for Board {
             if self.rows[i] == FILLED {
                 continue;
             }
-            if self.rows[current] == 0 {
-                return (i - current) as i32;
-            }
             self.rows[current] = self.rows[i];
             current += 1;
         }
@@ -37,168 +42,206 @@ impl BoardImpl for Board {
         }
         40 - current as i32
     }
-}
 
-impl Board {
     fn get(&self, x: i8, y: i8) -> bool {
         self.rows[y as usize] & 1 << x != 0
     }
 
-    #[cfg(test)]
-    pub fn fumenize(self) -> fumen::Fumen {
-        let mut fumen = fumen::Fumen::default();
-        let page = fumen.add_page();
-        for y in 0..23 {
-            for x in 0..10 {
-                if self.get(x as i8, y as i8) {
-                    page.field[y][x] = fumen::CellColor::Grey;
-                }
-            }
-        }
-        fumen
+    fn column_height(&self, x: i8) -> i8 {
+        self.rows
+            .iter()
+            .rposition(|&row| row & 1 << x != 0)
+            .map_or(0, |y| y as i8 + 1)
     }
-}
 
-impl Implementation for RowBits {
-    type Board = Board;
+    fn is_row_full(&self, y: i8) -> bool {
+        self.rows[y as usize] == FILLED
+    }
 
-    const NAME: &'static str = "row bits";
+    fn is_row_empty(&self, y: i8) -> bool {
+        self.rows[y as usize] == 0
+    }
 
-fn suggest(board: &Board, piece: Piece) -> Option<PieceLocation> {
-    let mut best = None;
+    fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&row| row == 0)
+    }
 
-    for &rotation in piece.sensible_rotations() {
-        for x in 0..10 {
-            let mut piece = PieceLocation {
-                piece,
-                rotation,
-                x,
-                y: 37,
-            };
+    fn corners(&self, x: i8, y: i8) -> u8 {
+        // the row with the walls added, so that bit x + 1 is column x
+        let row = |y: i8| match y {
+            y if y < 0 => !0,
+            y if y >= 40 => 1 | 1 << 11,
+            y => (self.rows[y as usize] as u32) << 1 | 1 | 1 << 11,
+        };
+        let above = row(y + 1) >> x;
+        let below = row(y - 1) >> x;
+        (above & 1 | (above >> 2 & 1) << 1 | (below & 1) << 2 | (below >> 2 & 1) << 3) as u8
+    }
 
-            if blocked(board, piece) {
-                continue;
-            }
+    fn add_garbage_holes(&mut self, rows: u32, holes: u16) -> Result<(), TopOut> {
+        let rows = rows.min(40) as usize;
+        let topped_out = self.rows[40 - rows..].iter().any(|&row| row != 0);
+        self.rows.copy_within(..40 - rows, rows);
+        for row in &mut self.rows[..rows] {
+            *row = FILLED & !holes;
+        }
+        if topped_out {
+            Err(TopOut::GarbageOut)
+        } else {
+            Ok(())
+        }
+    }
 
-            while !blocked(board, piece) {
-                piece.y -= 1;
+    fn from_cells(cells: &CellGrid) -> Self {
+        let mut board = Board::new();
+        for (y, row) in cells.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                board.rows[y] |= (cell as u16) << x;
             }
-            piece.y += 1;
+        }
+        board
+    }
+}
+
+impl Board {
+    /// The number of rows up to the highest filled cell, which can have empty rows below it.
+    fn highest(&self) -> i8 {
+        self.rows
+            .iter()
+            .rposition(|&row| row != 0)
+            .map_or(0, |y| y as i8 + 1)
+    }
+}
 
-            let mut board = *board;
-            board.place(piece);
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        notation::write_board(self, f)
+    }
+}
 
-            let piece_cells_eliminated = piece
-                .cells()
-                .iter()
-                .filter(|&&(_, y)| board.rows[y as usize] == FILLED)
-                .count() as i32;
+impl FromStr for Board {
+    type Err = ParseBoardError;
 
-            let lines_cleared = board.collapse_lines();
+    fn from_str(s: &str) -> Result<Self, ParseBoardError> {
+        notation::parse_board(s)
+    }
+}
 
-            let mut low = 40;
-            let mut high = 0;
-            for &(_, y) in &piece.cells() {
-                low = low.min(y);
-                high = high.max(y);
-            }
+impl Implementation for RowBits {
+    type Board = Board;
+
+    const NAME: &'static str = "row bits";
+
+    fn for_each_placement(board: &Board, piece: Piece, mut f: impl FnMut(&Board, &PlacementInfo)) {
+        for &rotation in piece.sensible_rotations() {
+            for x in 0..10 {
+                let mut piece = PieceLocation {
+                    piece,
+                    rotation,
+                    x,
+                    // high enough that every cell starts above the board
+                    y: 42,
+                };
+
+                if blocked(board, piece) {
+                    continue;
+                }
 
-            let landing_height = low as i32 + high as i32;
-            let eroded_piece_cells_metric = lines_cleared * piece_cells_eliminated;
-            let row_transitions = row_transitions(&board);
-            let column_transitions = column_transitions(&board);
-            let buried_holes = buried_holes(&board);
-            let wells = wells(&board);
-
-            let score = 2 * eroded_piece_cells_metric
-                - landing_height
-                - 2 * row_transitions
-                - 2 * column_transitions
-                - 8 * buried_holes
-                - 2 * wells;
-
-            match best {
-                None => best = Some((piece, score)),
-                Some((_, s)) => {
-                    if score > s {
-                        best = Some((piece, score))
-                    }
+                while !blocked(board, piece) {
+                    piece.y -= 1;
                 }
+                piece.y += 1;
+
+                if piece.cells().iter().any(|&(_, y)| y >= 40) {
+                    continue;
+                }
+
+                let mut board = *board;
+                board.place(piece);
+
+                let piece_cells_eliminated = piece
+                    .cells()
+                    .iter()
+                    .filter(|&&(_, y)| board.rows[y as usize] == FILLED)
+                    .count() as i32;
+
+                let lines_cleared = board.collapse_lines();
+
+                f(
+                    &board,
+                    &PlacementInfo {
+                        location: piece,
+                        lines_cleared,
+                        piece_cells_eliminated,
+                        perfect_clear: lines_cleared > 0 && board.is_empty(),
+                    },
+                );
             }
         }
     }
-
-    best.map(|(p, _)| p)
-}
 }
 
 fn blocked(board: &Board, piece: PieceLocation) -> bool {
     for &(x, y) in &piece.cells() {
-        if x < 0 || x >= 10 || y < 0 || y >= 40 {
+        if !(0..10).contains(&x) || y < 0 {
             return true;
         }
-        if board.get(x, y) {
+        if y < 40 && board.get(x, y) {
             return true;
         }
     }
     false
 }
 
-fn row_transitions(board: &Board) -> i32 {
-    let mut count = 0;
-    for (y, &row) in board.rows.iter().enumerate() {
-        if row == 0 {
-            count += (40 - y as i32) * 2;
-            break;
+impl Features for Board {
+    fn row_transitions(&self) -> i32 {
+        let highest = self.highest();
+        let mut count = (40 - highest as i32) * 2;
+        for &row in &self.rows[..highest as usize] {
+            let row = row | !FILLED;
+            let transitions = row ^ (row << 1 | 1);
+            count += transitions.count_ones() as i32;
         }
-        let row = row | !FILLED;
-        let transitions = row ^ (row << 1 | 1);
-        count += transitions.count_ones() as i32;
+        count
     }
-    count
-}
 
-fn column_transitions(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut previous = FILLED;
-    for &row in &board.rows {
-        count += (row ^ previous).count_ones();
-        previous = row;
-        if row == 0 {
-            break;
+    fn column_transitions(&self) -> i32 {
+        let mut count = 0;
+        let mut previous = FILLED;
+        let stack = &self.rows[..self.highest() as usize];
+        for &row in stack.iter().chain(std::iter::once(&0)) {
+            count += (row ^ previous).count_ones();
+            previous = row;
         }
+        count as i32
     }
-    count as i32
-}
 
-fn buried_holes(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut is_column_covered = 0;
-    for row in board.rows.iter().rev() {
-        count += (is_column_covered & !row).count_ones();
-        is_column_covered |= row;
+    fn buried_holes(&self) -> i32 {
+        let mut count = 0;
+        let mut is_column_covered = 0;
+        for row in self.rows.iter().rev() {
+            count += (is_column_covered & !row).count_ones();
+            is_column_covered |= row;
+        }
+        count as i32
     }
-    count as i32
-}
 
-fn wells(board: &Board) -> i32 {
-    let mut score = 0;
-    for y in 0..40 {
-        let row = board.rows[y];
-        // Locate the well cells in this row
-        let mut well_cells = (row >> 1 | 1 << 9) & !row & (row << 1 | 1);
-        score += well_cells.count_ones();
-        for j in (0..y).rev() {
-            // Mask off the well cells that hit the ground
-            well_cells &= !board.rows[j];
-            if well_cells == 0 {
-                break;
-            }
+    fn wells(&self) -> i32 {
+        let mut score = 0;
+        for y in 0..self.highest() as usize {
+            let row = self.rows[y];
+            // Locate the well cells in this row
+            let mut well_cells = (row >> 1 | 1 << 9) & !row & (row << 1 | 1);
             score += well_cells.count_ones();
+            for j in (0..y).rev() {
+                // Mask off the well cells that hit the ground
+                well_cells &= !self.rows[j];
+                if well_cells == 0 {
+                    break;
+                }
+                score += well_cells.count_ones();
+            }
         }
-        if row == 0 {
-            break;
-        }
+        score as i32
     }
-    score as i32
 }
@@ -1,4 +1,12 @@
-use crate::{common::*, BoardImpl, Implementation};
+use crate::{
+    common::*,
+    eval::PlacementInfo,
+    notation::{self, ParseBoardError},
+    rules::TopOut,
+    BoardImpl, Features, Implementation,
+};
+use std::fmt;
+use std::str::FromStr;
 
 pub struct RowBitsColHeights;
 
@@ -47,29 +55,97 @@ impl BoardImpl for Board {
         }
         rows_cleared as i32
     }
-}
 
-impl Board {
     fn get(&self, x: i8, y: i8) -> bool {
         self.rows[y as usize] & 1 << x != 0
     }
 
-    fn highest(&self) -> i8 {
-        self.col_heights.iter().copied().max().unwrap()
+    fn column_height(&self, x: i8) -> i8 {
+        self.col_heights[x as usize]
+    }
+
+    fn is_row_full(&self, y: i8) -> bool {
+        self.rows[y as usize] == FILLED
+    }
+
+    fn is_row_empty(&self, y: i8) -> bool {
+        self.rows[y as usize] == 0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.highest() == 0
+    }
+
+    fn corners(&self, x: i8, y: i8) -> u8 {
+        // the row with the walls added, so that bit x + 1 is column x
+        let row = |y: i8| match y {
+            y if y < 0 => !0,
+            y if y >= 40 => 1 | 1 << 11,
+            y => (self.rows[y as usize] as u32) << 1 | 1 | 1 << 11,
+        };
+        let above = row(y + 1) >> x;
+        let below = row(y - 1) >> x;
+        (above & 1 | (above >> 2 & 1) << 1 | (below & 1) << 2 | (below >> 2 & 1) << 3) as u8
     }
 
-    #[cfg(test)]
-    pub fn fumenize(self) -> fumen::Fumen {
-        let mut fumen = fumen::Fumen::default();
-        let page = fumen.add_page();
-        for y in 0..23 {
-            for x in 0..10 {
-                if self.get(x as i8, y as i8) {
-                    page.field[y][x] = fumen::CellColor::Grey;
+    fn add_garbage_holes(&mut self, rows: u32, holes: u16) -> Result<(), TopOut> {
+        let rows = rows.min(40) as usize;
+        let topped_out = self.highest() as usize + rows > 40;
+        self.rows.copy_within(..40 - rows, rows);
+        for row in &mut self.rows[..rows] {
+            *row = FILLED & !holes;
+        }
+        for x in 0..10 {
+            let height = self.col_heights[x] as usize;
+            self.col_heights[x] = if height + rows > 40 {
+                (0..40)
+                    .rev()
+                    .find(|&y| self.rows[y] & 1 << x != 0)
+                    .map_or(0, |y| y as i8 + 1)
+            } else if height > 0 || holes & 1 << x == 0 {
+                (height + rows) as i8
+            } else {
+                0
+            };
+        }
+        if topped_out {
+            Err(TopOut::GarbageOut)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn from_cells(cells: &CellGrid) -> Self {
+        let mut board = Board::new();
+        for (y, row) in cells.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if cell {
+                    board.rows[y] |= 1 << x;
+                    board.col_heights[x] = y as i8 + 1;
                 }
             }
         }
-        fumen
+        board
+    }
+}
+
+impl Board {
+    fn highest(&self) -> i8 {
+        self.col_heights.iter().copied().max().unwrap()
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        notation::write_board(self, f)
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, ParseBoardError> {
+        notation::parse_board(s)
     }
 }
 
@@ -78,9 +154,7 @@ impl Implementation for RowBitsColHeights {
 
     const NAME: &'static str = "row bits + col heights";
 
-    fn suggest(board: &Board, piece: Piece) -> Option<PieceLocation> {
-        let mut best = None;
-
+    fn for_each_placement(board: &Board, piece: Piece, mut f: impl FnMut(&Board, &PlacementInfo)) {
         for &rotation in piece.sensible_rotations() {
             'placement: for x in 0..10 {
                 let mut piece = PieceLocation {
@@ -91,13 +165,17 @@ impl Implementation for RowBitsColHeights {
                 };
 
                 for &(x, y) in &piece.cells() {
-                    if x < 0 || x >= 10 {
+                    if !(0..10).contains(&x) {
                         continue 'placement;
                     }
 
                     piece.y = piece.y.max(board.col_heights[x as usize] - y);
                 }
 
+                if piece.cells().iter().any(|&(_, y)| y >= 40) {
+                    continue;
+                }
+
                 let mut board = *board;
                 board.place(piece);
 
@@ -109,93 +187,73 @@ impl Implementation for RowBitsColHeights {
 
                 let lines_cleared = board.collapse_lines();
 
-                let mut low = 40;
-                let mut high = 0;
-                for &(_, y) in &piece.cells() {
-                    low = low.min(y);
-                    high = high.max(y);
-                }
-
-                let landing_height = low as i32 + high as i32;
-                let eroded_piece_cells_metric = lines_cleared * piece_cells_eliminated;
-                let row_transitions = row_transitions(&board);
-                let column_transitions = column_transitions(&board);
-                let buried_holes = buried_holes(&board);
-                let wells = wells(&board);
-
-                let score = 2 * eroded_piece_cells_metric
-                    - landing_height
-                    - 2 * row_transitions
-                    - 2 * column_transitions
-                    - 8 * buried_holes
-                    - 2 * wells;
-
-                match best {
-                    None => best = Some((piece, score)),
-                    Some((_, s)) => {
-                        if score > s {
-                            best = Some((piece, score))
-                        }
-                    }
-                }
+                f(
+                    &board,
+                    &PlacementInfo {
+                        location: piece,
+                        lines_cleared,
+                        piece_cells_eliminated,
+                        perfect_clear: lines_cleared > 0 && board.is_empty(),
+                    },
+                );
             }
         }
-
-        best.map(|(p, _)| p)
     }
 }
 
-fn row_transitions(board: &Board) -> i32 {
-    let highest = board.highest();
-    let extras = (40 - highest as i32) * 2;
-    board
-        .rows
-        .iter()
-        .take(highest as usize)
-        .map(|&row| {
-            let row = row | !FILLED;
-            let transitions = row ^ (row << 1 | 1);
-            transitions.count_ones() as i32
-        })
-        .sum::<i32>()
-        + extras
-}
+impl Features for Board {
+    fn row_transitions(&self) -> i32 {
+        let highest = self.highest();
+        let extras = (40 - highest as i32) * 2;
+        self.rows
+            .iter()
+            .take(highest as usize)
+            .map(|&row| {
+                let row = row | !FILLED;
+                let transitions = row ^ (row << 1 | 1);
+                transitions.count_ones() as i32
+            })
+            .sum::<i32>()
+            + extras
+    }
 
-fn column_transitions(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut previous = FILLED;
-    for &row in &board.rows[..board.highest() as usize + 1] {
-        count += (row ^ previous).count_ones();
-        previous = row;
+    fn column_transitions(&self) -> i32 {
+        let mut count = 0;
+        let mut previous = FILLED;
+        let stack = &self.rows[..self.highest() as usize];
+        for &row in stack.iter().chain(std::iter::once(&0)) {
+            count += (row ^ previous).count_ones();
+            previous = row;
+        }
+        count as i32
     }
-    count as i32
-}
 
-fn buried_holes(board: &Board) -> i32 {
-    let mut count = 0;
-    let mut is_column_covered = 0;
-    for row in board.rows[..board.highest() as usize].iter().rev() {
-        count += (is_column_covered & !row).count_ones();
-        is_column_covered |= row;
+    fn buried_holes(&self) -> i32 {
+        let mut count = 0;
+        let mut is_column_covered = 0;
+        for row in self.rows[..self.highest() as usize].iter().rev() {
+            count += (is_column_covered & !row).count_ones();
+            is_column_covered |= row;
+        }
+        count as i32
     }
-    count as i32
-}
 
-fn wells(board: &Board) -> i32 {
-    let mut score = 0;
-    for y in 0..board.highest() as usize {
-        let row = board.rows[y];
-        // Locate the well cells in this row
-        let mut well_cells = (row >> 1 | 1 << 9) & !row & (row << 1 | 1);
-        score += well_cells.count_ones();
-        for j in (0..y).rev() {
-            // Mask off the well cells that hit the ground
-            well_cells &= !board.rows[j];
-            if well_cells == 0 {
-                break;
-            }
+    fn wells(&self) -> i32 {
+        let mut score = 0;
+        for y in 0..self.highest() as usize {
+            let row = self.rows[y];
+            // Locate the well cells in this row
+            let mut well_cells = (row >> 1 | 1 << 9) & !row & (row << 1 | 1);
             score += well_cells.count_ones();
+            for j in (0..y).rev() {
+                // Mask off the well cells that hit the ground
+                well_cells &= !self.rows[j];
+                if well_cells == 0 {
+                    break;
+                }
+                score += well_cells.count_ones();
+            }
         }
+        score as i32
     }
-    score as i32
 }
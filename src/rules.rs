@@ -0,0 +1,203 @@
+//! Guideline spawn position, visible field and top out rules.
+
+use crate::common::*;
+use crate::movegen::{self, DropMode, Placement};
+use crate::rotation::RotationSystem;
+use crate::BoardImpl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rules {
+    /// Where the pivot of a north facing piece spawns.
+    pub spawn_x: i8,
+    pub spawn_y: i8,
+    /// The number of rows of the field that are visible. A piece that locks entirely above them
+    /// tops out.
+    pub visible_height: i8,
+    /// Whether pieces can be rotated 180 degrees.
+    pub allow_180: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            spawn_x: SPAWN_X,
+            spawn_y: SPAWN_Y,
+            visible_height: 20,
+            allow_180: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TopOut {
+    /// A new piece overlaps the stack when it spawns.
+    BlockOut,
+    /// A piece locked with every cell above the visible field.
+    LockOut,
+    /// Garbage pushed filled cells past the top of the board.
+    GarbageOut,
+}
+
+impl std::fmt::Display for TopOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TopOut::BlockOut => write!(f, "piece spawned overlapping the stack"),
+            TopOut::LockOut => write!(f, "piece locked above the visible field"),
+            TopOut::GarbageOut => write!(f, "garbage pushed the stack past the top of the board"),
+        }
+    }
+}
+
+impl std::error::Error for TopOut {}
+
+impl Rules {
+    pub fn spawn_location(&self, piece: Piece) -> PieceLocation {
+        PieceLocation {
+            piece,
+            rotation: Rotation::North,
+            x: self.spawn_x,
+            y: self.spawn_y,
+        }
+    }
+
+    /// The spawn location of the piece, or `BlockOut` if its shape in the rotation system
+    /// overlaps the stack there.
+    pub fn spawn<R: RotationSystem, B: BoardImpl>(
+        &self,
+        system: &R,
+        board: &B,
+        piece: Piece,
+    ) -> Result<PieceLocation, TopOut> {
+        let location = self.spawn_location(piece);
+        if system.collides(board, location) {
+            Err(TopOut::BlockOut)
+        } else {
+            Ok(location)
+        }
+    }
+
+    pub fn is_lock_out(&self, location: PieceLocation) -> bool {
+        location
+            .cells()
+            .iter()
+            .all(|&(_, y)| y >= self.visible_height)
+    }
+
+    /// Every placement reachable from this spawn position with the rotation system, or
+    /// `BlockOut` if the piece can't spawn.
+    pub fn find_placements<R: RotationSystem, B: BoardImpl>(
+        &self,
+        system: &R,
+        board: &B,
+        piece: Piece,
+        mode: DropMode,
+    ) -> Result<Vec<Placement>, TopOut> {
+        let spawn = self.spawn(system, board, piece)?;
+        Ok(movegen::find_placements_from(
+            system,
+            board,
+            spawn,
+            mode,
+            self.allow_180,
+        ))
+    }
+
+    /// Places the piece and clears lines, returning the number of lines cleared. The piece is
+    /// placed even if it locks out.
+    pub fn lock<B: BoardImpl>(
+        &self,
+        board: &mut B,
+        location: PieceLocation,
+    ) -> Result<i32, TopOut> {
+        board.place(location);
+        let lines_cleared = board.collapse_lines();
+        if self.is_lock_out(location) {
+            Err(TopOut::LockOut)
+        } else {
+            Ok(lines_cleared)
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn block_out() {
+    use crate::naive;
+    use crate::rotation::Srs;
+
+    let rules = Rules::default();
+    let mut board = naive::Board::new();
+    assert!(rules.spawn(&Srs, &board, Piece::T).is_ok());
+    board.place(PieceLocation {
+        piece: Piece::I,
+        rotation: Rotation::East,
+        x: 4,
+        y: 20,
+    });
+    assert_eq!(rules.spawn(&Srs, &board, Piece::T), Err(TopOut::BlockOut));
+    assert_eq!(
+        rules.find_placements(&Srs, &board, Piece::T, DropMode::HardDrop),
+        Err(TopOut::BlockOut)
+    );
+
+    let low = Rules {
+        spawn_y: 10,
+        ..rules
+    };
+    assert_eq!(
+        low.spawn(&Srs, &board, Piece::O),
+        Ok(PieceLocation {
+            piece: Piece::O,
+            rotation: Rotation::North,
+            x: 4,
+            y: 10,
+        })
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn block_out_uses_rotation_system() {
+    use crate::naive;
+    use crate::rotation::{Ars, Srs};
+
+    // an ARS T spawns pointing down, an SRS T pointing up
+    let rules = Rules::default();
+    let mut cells = [[false; 10]; 40];
+    cells[19][4] = true;
+    let below = naive::Board::from_cells(&cells);
+    cells[19][4] = false;
+    cells[21][4] = true;
+    let above = naive::Board::from_cells(&cells);
+
+    assert_eq!(
+        rules.find_placements(&Ars, &below, Piece::T, DropMode::HardDrop),
+        Err(TopOut::BlockOut)
+    );
+    assert!(rules.spawn(&Srs, &below, Piece::T).is_ok());
+    assert!(rules
+        .find_placements(&Ars, &above, Piece::T, DropMode::HardDrop)
+        .is_ok());
+    assert_eq!(rules.spawn(&Srs, &above, Piece::T), Err(TopOut::BlockOut));
+}
+
+#[cfg(test)]
+#[test]
+fn lock_out() {
+    use crate::naive;
+
+    let rules = Rules::default();
+    let mut board = naive::Board::new();
+    let partly_visible = PieceLocation {
+        piece: Piece::T,
+        rotation: Rotation::North,
+        x: 4,
+        y: 19,
+    };
+    assert_eq!(rules.lock(&mut board, partly_visible), Ok(0));
+    let above = PieceLocation {
+        y: 21,
+        ..partly_visible
+    };
+    assert_eq!(rules.lock(&mut board, above), Err(TopOut::LockOut));
+}
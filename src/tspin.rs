@@ -0,0 +1,117 @@
+//! T-spin detection using the 3-corner rule.
+
+use crate::common::*;
+use crate::rotation::{RotationSystem, Srs};
+use crate::BoardImpl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+/// Classifies a T placement. `last_rotation` is the state the piece rotated from and the index
+/// of the kick test used if the last move was a rotation, and `None` otherwise.
+///
+/// At least 3 of the corners around the center of the T must be filled. It is a full T-spin if
+/// both corners on the pointing side are filled, or if the last rotation used the fifth kick test
+/// of a quarter turn (the TST kick upgrade); otherwise it is a mini.
+pub fn classify<B: BoardImpl>(
+    board: &B,
+    location: PieceLocation,
+    last_rotation: Option<(Rotation, usize)>,
+) -> TSpin {
+    classify_with(&Srs, board, location, last_rotation)
+}
+
+/// Like `classify`, for a location using the shapes of the rotation system, which also decides
+/// which kicks upgrade a mini to a full T-spin.
+pub fn classify_with<R: RotationSystem, B: BoardImpl>(
+    system: &R,
+    board: &B,
+    location: PieceLocation,
+    last_rotation: Option<(Rotation, usize)>,
+) -> TSpin {
+    let (from, kick) = match last_rotation {
+        Some(last_rotation) if location.piece == Piece::T => last_rotation,
+        _ => return TSpin::None,
+    };
+
+    let to = location.rotation;
+    // the center of a T with the canonical shapes is its position
+    let location = system.normalize(location);
+    let corners = board.corners(location.x, location.y);
+    if corners.count_ones() < 3 {
+        return TSpin::None;
+    }
+
+    let front = match location.rotation {
+        Rotation::North => CORNER_NW | CORNER_NE,
+        Rotation::East => CORNER_NE | CORNER_SE,
+        Rotation::South => CORNER_SW | CORNER_SE,
+        Rotation::West => CORNER_NW | CORNER_SW,
+    };
+    if corners & front == front || system.upgrades_tspin(from, to, kick) {
+        TSpin::Full
+    } else {
+        TSpin::Mini
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn mini_and_upgrade() {
+    use crate::naive;
+
+    let board: naive::Board = "
+        #.........
+        ..........
+    "
+    .parse()
+    .unwrap();
+    let location = PieceLocation {
+        piece: Piece::T,
+        rotation: Rotation::North,
+        x: 1,
+        y: 0,
+    };
+    assert_eq!(classify(&board, location, None), TSpin::None);
+    assert_eq!(
+        classify(&board, location, Some((Rotation::East, 0))),
+        TSpin::Mini
+    );
+    assert_eq!(
+        classify(&board, location, Some((Rotation::East, 4))),
+        TSpin::Full
+    );
+    // only quarter turns get the upgrade
+    assert_eq!(
+        classify(&board, location, Some((Rotation::South, 4))),
+        TSpin::Mini
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn tspin_double() {
+    use crate::naive;
+
+    let board: naive::Board = "
+        ###.......
+        ##...#####
+        ###.######
+    "
+    .parse()
+    .unwrap();
+    let location = PieceLocation {
+        piece: Piece::T,
+        rotation: Rotation::South,
+        x: 3,
+        y: 1,
+    };
+    assert_eq!(
+        classify(&board, location, Some((Rotation::West, 0))),
+        TSpin::Full
+    );
+}